@@ -0,0 +1,174 @@
+#!/usr/bin/env bash
+
+# Readline's bind -x with READLINE_LINE needs bash 4
+if [[ $- != *i* ]] || (( BASH_VERSINFO[0] < 4 )); then
+  return 0 2>/dev/null || exit 0
+fi
+
+CHITIN_ECHO_PROMPT=${CHITIN_ECHO_PROMPT:-1}
+CHITIN_HISTORY_KEY=${CHITIN_HISTORY_KEY:-'\C-xc'}
+# Send the previous command's output with a prompt: 0 never, 1 only for
+# commands run through chitin_capture, tmux also falls back to the pane scrollback
+CHITIN_CAPTURE=${CHITIN_CAPTURE:-0}
+CHITIN_CAPTURE_LINES=${CHITIN_CAPTURE_LINES:-200}
+# Captured output lives in a directory private to this shell, made once and
+# removed on exit, so no one else can plant links there or read it. Without
+# one there is nowhere safe to keep output, so capturing stays off.
+if [[ -n ${_CHITIN_TMPDIR:-} && -d $_CHITIN_TMPDIR && -O $_CHITIN_TMPDIR ]] ||
+  _CHITIN_TMPDIR=$(command mktemp -d "${TMPDIR:-/tmp}/chitin.XXXXXXXXXX" 2>/dev/null); then
+  _CHITIN_CAPTURE_FILE="$_CHITIN_TMPDIR/capture"
+else
+  echo "chitin: cannot create a directory in ${TMPDIR:-/tmp}, output capture is disabled" >&2
+  _CHITIN_TMPDIR="" _CHITIN_CAPTURE_FILE=""
+fi
+_CHITIN_CAPTURE_STATUS=0
+# One session per terminal, so unrelated tabs do not share prompt history.
+# An explicitly set CHITIN_SESSION_ID is kept; one generated by a parent shell is not.
+if [[ -z "${CHITIN_SESSION_ID:-}" || -n "${_CHITIN_SESSION_AUTO:-}" ]]; then
+  _chitin_tty=$(tty 2>/dev/null) || _chitin_tty=notty
+  export CHITIN_SESSION_ID="${USER:-user}@${_chitin_tty#/dev/}:$$"
+  export _CHITIN_SESSION_AUTO=1
+  unset _chitin_tty
+fi
+# Last generated command, until we know whether it was run
+_CHITIN_PENDING=""
+
+# Report what became of a generated command for the audit log, without blocking the prompt
+_chitin_report() {
+  (command chitin outcome "$1" --command "$2" >/dev/null 2>&1 &)
+}
+
+# Run a command with its output shown as usual and also kept for the next "@" prompt
+chitin_capture() {
+  if (( ! $# )); then
+    echo "usage: chitin_capture command [args...]" >&2
+    return 2
+  fi
+  if [[ -z $_CHITIN_CAPTURE_FILE ]]; then
+    "$@"
+    return
+  fi
+  ( umask 077; : >| "$_CHITIN_CAPTURE_FILE" )
+  "$@" 2>&1 | command tee -- "$_CHITIN_CAPTURE_FILE"
+  _CHITIN_CAPTURE_STATUS=${PIPESTATUS[0]}
+  return "$_CHITIN_CAPTURE_STATUS"
+}
+
+# Per-shell switches; the argument of chitin_capture_on is the CHITIN_CAPTURE mode
+chitin_capture_on() { CHITIN_CAPTURE=${1:-1}; }
+chitin_capture_off() {
+  CHITIN_CAPTURE=0
+  [[ -n $_CHITIN_CAPTURE_FILE ]] && command rm -f -- "$_CHITIN_CAPTURE_FILE"
+}
+
+# Set CHITIN_REPLY to the `chitin ask` arguments carrying the captured output, if any.
+# A user defined chitin_redact function filters the output (stdin to stdout) first.
+_chitin_capture_args() {
+  CHITIN_REPLY=()
+  [[ "$CHITIN_CAPTURE" == 0 || -z $_CHITIN_CAPTURE_FILE ]] && return
+  local file="$_CHITIN_CAPTURE_FILE" status=$_CHITIN_CAPTURE_STATUS
+  if [[ ! -s "$file" && "$CHITIN_CAPTURE" == tmux && -n "${TMUX:-}" ]]; then
+    file="$file.pane"
+    status=0
+    ( umask 077; command tmux capture-pane -p -J -S "-$CHITIN_CAPTURE_LINES" >| "$file" ) 2>/dev/null
+  fi
+  [[ -s "$file" ]] || return
+  if declare -F chitin_redact >/dev/null; then
+    ( umask 077; chitin_redact < "$file" >| "$file.redacted" ) &&
+      command mv -f -- "$file.redacted" "$file"
+  fi
+  CHITIN_REPLY=(--output-file "$file" --exit-status "$status")
+}
+
+# Runs on Enter before readline acts on the line. Decides, by rebinding
+# \C-x\C-b, whether the Enter macro goes on to accept the line.
+_chitin_accept_line() {
+  local line="$READLINE_LINE"
+  if [[ "$line" != @* ]]; then
+    if [[ -n "$_CHITIN_PENDING" && -n "$line" ]]; then
+      if [[ "$line" == "$_CHITIN_PENDING" ]]; then
+        _chitin_report executed "$line"
+      else
+        _chitin_report edited "$line"
+      fi
+      _CHITIN_PENDING=""
+    fi
+    # Output of an earlier command no longer belongs to the previous one
+    if [[ -n "$line" && "$line" != chitin_capture* && -e "$_CHITIN_CAPTURE_FILE" ]]; then
+      command rm -f -- "$_CHITIN_CAPTURE_FILE"
+    fi
+    bind '"\C-x\C-b": accept-line'
+    return
+  fi
+
+  if [[ -n "$_CHITIN_PENDING" ]]; then
+    _chitin_report discarded ""
+    _CHITIN_PENDING=""
+  fi
+  history -s -- "$line"
+  if [[ "$CHITIN_ECHO_PROMPT" == 1 ]]; then
+    # Leave the prompt on screen; the spinner and the refilled line go below it
+    printf '%s\n' "$line" >&2
+  fi
+
+  local command=""
+  if command -v chitin >/dev/null 2>&1; then
+    local -a CHITIN_REPLY known
+    mapfile -t known < <(compgen -a -A function -X '_*')
+    _chitin_capture_args
+    command=$(command chitin ask "$line" --pwd "$PWD" --shell bash \
+      --shell-version "${BASH_VERSION%%[^0-9.]*}" "${known[@]/#/--alias=}" "${CHITIN_REPLY[@]}")
+    # Scrollback is read afresh for every prompt
+    [[ -n $_CHITIN_CAPTURE_FILE ]] && command rm -f -- "$_CHITIN_CAPTURE_FILE.pane"
+  else
+    echo "Chitin binary not found in PATH." >&2
+  fi
+
+  if [[ -n "$command" ]]; then
+    READLINE_LINE="$command"
+    READLINE_POINT=${#command}
+    _CHITIN_PENDING="$command"
+  else
+    READLINE_LINE=""
+    READLINE_POINT=0
+  fi
+  # Stay on the line so the generated command can be reviewed first
+  bind '"\C-x\C-b": redraw-current-line'
+}
+
+# Pull a previously generated command back into the line, searching for what
+# is already typed. Uses fzf to pick one when available, else the best match.
+_chitin_history_widget() {
+  local query="${READLINE_LINE#@}" selected
+  query="${query# }"
+  if command -v fzf >/dev/null 2>&1; then
+    selected=$(command chitin history --format commands -n 1000 2>/dev/null |
+      fzf --height=40% --reverse --no-sort --prompt='chitin> ' --query="$query")
+  else
+    selected=$(command chitin history --format commands -n 1 -- "$query" 2>/dev/null)
+  fi
+  if [[ -n "$selected" ]]; then
+    READLINE_LINE="$selected"
+    READLINE_POINT=${#selected}
+  fi
+}
+
+_chitin_cleanup() {
+  [[ -n $_CHITIN_TMPDIR ]] && command rm -rf -- "$_CHITIN_TMPDIR"
+}
+
+# Guard against multiple bindings
+if [[ -z "${CHITIN_BASH_LOADED:-}" ]]; then
+  # Enter runs the hook, then whatever it bound \C-x\C-b to
+  bind -x '"\C-x\C-a": _chitin_accept_line'
+  bind '"\C-x\C-b": accept-line'
+  bind '"\C-m": "\C-x\C-a\C-x\C-b"'
+  bind '"\C-j": "\C-x\C-a\C-x\C-b"'
+  bind -x "\"$CHITIN_HISTORY_KEY\": _chitin_history_widget"
+  # Run before an existing EXIT trap rather than replacing it
+  _chitin_trap=$(trap -p EXIT)
+  [[ -n $_chitin_trap ]] && _chitin_trap=$(eval "set -- $_chitin_trap"; printf '%s' "$3")
+  trap -- "_chitin_cleanup${_chitin_trap:+; $_chitin_trap}" EXIT
+  unset _chitin_trap
+  CHITIN_BASH_LOADED=1
+fi
@@ -0,0 +1,108 @@
+# Chitin integration for fish, installed into ~/.config/fish/conf.d/
+status is-interactive; or exit
+
+set -q CHITIN_HISTORY_KEY; or set -g CHITIN_HISTORY_KEY \cxc
+# One session per terminal, so unrelated tabs do not share prompt history.
+# An explicitly set CHITIN_SESSION_ID is kept; one generated by a parent shell is not.
+if not set -q CHITIN_SESSION_ID; or set -q _CHITIN_SESSION_AUTO
+    set -l user $USER
+    test -n "$user"; or set user user
+    set -l tty (tty 2>/dev/null); or set tty notty
+    set -gx CHITIN_SESSION_ID "$user@"(string replace -r '^/dev/' '' -- $tty)":$fish_pid"
+    set -gx _CHITIN_SESSION_AUTO 1
+end
+# Last generated command, until we know whether it was run
+set -g _chitin_pending ""
+
+# Report what became of a generated command for the audit log, without blocking the prompt
+function __chitin_report
+    command chitin outcome $argv[1] --command $argv[2] >/dev/null 2>&1 &
+    disown 2>/dev/null
+end
+
+# fish has no way to add a line to history without running it, so write the
+# entry to the history file and merge it into this session
+function __chitin_save_history --argument-names entry
+    set -q fish_history; or set -l fish_history fish
+    test -n "$fish_history"; or return
+    set -l data_dir $XDG_DATA_HOME
+    test -n "$data_dir"; or set data_dir ~/.local/share
+    set -l escaped (string replace -a -- '\\' '\\\\' $entry | string join '\\n')
+    printf '%s\n' "- cmd: $escaped" "  when: "(date +%s) >>$data_dir/fish/{$fish_history}_history
+    and builtin history merge
+end
+
+function __chitin_preexec --on-event fish_preexec
+    set -l line $argv[1]
+    test -n "$_chitin_pending"; or return
+    if test "$line" = "$_chitin_pending"
+        __chitin_report executed $line
+    else
+        __chitin_report edited $line
+    end
+    set -g _chitin_pending ""
+end
+
+function __chitin_accept_line
+    set -l line (commandline | string collect)
+    if not string match -q -- '@*' $line
+        commandline -f execute
+        return
+    end
+
+    if test -n "$_chitin_pending"
+        __chitin_report discarded ""
+        set -g _chitin_pending ""
+    end
+    __chitin_save_history $line
+
+    set -l generated
+    if command -q chitin
+        # Start a new line so the spinner doesn't overwrite the prompt
+        echo >&2
+        set generated (command chitin ask $line --pwd $PWD --shell fish --shell-version $version \
+            --alias=(functions -n) | string collect)
+    else
+        echo "Chitin binary not found in PATH." >&2
+    end
+
+    if test -n "$generated"
+        commandline -r -- $generated
+        commandline -C (string length -- $generated)
+        set -g _chitin_pending $generated
+    else
+        commandline -r ""
+    end
+    commandline -f repaint
+end
+
+# Pull a previously generated command back into the line, searching for what
+# is already typed. Uses fzf to pick one when available, else the best match.
+function __chitin_history_widget
+    set -l query (string replace -r '^@ ?' '' -- (commandline | string collect))
+    set -l selected
+    if command -q fzf
+        set selected (command chitin history --format commands -n 1000 2>/dev/null |
+            fzf --height=40% --reverse --no-sort --prompt='chitin> ' --query="$query")
+    else
+        set selected (command chitin history --format commands -n 1 -- "$query" 2>/dev/null)
+    end
+    if test -n "$selected"
+        commandline -r -- $selected
+        commandline -f end-of-line
+    end
+    commandline -f repaint
+end
+
+function __chitin_key_bindings
+    for mode in default insert
+        bind -M $mode \r __chitin_accept_line
+        bind -M $mode $CHITIN_HISTORY_KEY __chitin_history_widget
+    end
+end
+
+# Key bindings are reset whenever fish_key_bindings changes, so bind again then
+function __chitin_rebind --on-variable fish_key_bindings
+    __chitin_key_bindings
+end
+__chitin_key_bindings
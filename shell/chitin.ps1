@@ -0,0 +1,41 @@
+# Chitin integration for PowerShell (pwsh) through PSReadLine, dot-sourced
+# from the profile.
+
+# One session per terminal, so unrelated tabs do not share prompt history.
+# An explicitly set CHITIN_SESSION_ID is kept; one generated by a parent shell is not.
+if (-not $env:CHITIN_SESSION_ID -or $env:_CHITIN_SESSION_AUTO) {
+    $chitinTty = (& tty 2>$null)
+    if ($LASTEXITCODE -ne 0 -or -not $chitinTty) { $chitinTty = 'notty' }
+    $chitinUser = if ($env:USER) { $env:USER } else { 'user' }
+    $env:CHITIN_SESSION_ID = "$chitinUser@$($chitinTty -replace '^/dev/', ''):$PID"
+    $env:_CHITIN_SESSION_AUTO = '1'
+    Remove-Variable chitinTty, chitinUser
+}
+
+if (Get-Module -ListAvailable -Name PSReadLine) {
+    # Bound to Enter: "@" lines are replaced by the generated command, anything
+    # else is run as usual
+    Set-PSReadLineKeyHandler -Key Enter -BriefDescription ChitinAcceptLine -ScriptBlock {
+        $line = $null
+        $cursor = $null
+        [Microsoft.PowerShell.PSConsoleReadLine]::GetBufferState([ref]$line, [ref]$cursor)
+        if (-not $line.StartsWith('@')) {
+            [Microsoft.PowerShell.PSConsoleReadLine]::AcceptLine()
+            return
+        }
+
+        [Microsoft.PowerShell.PSConsoleReadLine]::AddToHistory($line)
+        $chitin = Get-Command chitin -CommandType Application -ErrorAction SilentlyContinue |
+            Select-Object -First 1
+        if (-not $chitin) {
+            [Console]::Error.WriteLine('Chitin binary not found in PATH.')
+            return
+        }
+        # Start a new line so the spinner doesn't overwrite the prompt
+        [Console]::Error.WriteLine()
+        $version = "$($PSVersionTable.PSVersion)"
+        $generated = (& $chitin.Source ask $line --pwd $PWD.ProviderPath --shell pwsh --shell-version $version) -join "`n"
+        [Microsoft.PowerShell.PSConsoleReadLine]::Replace(0, $line.Length, $generated)
+        [Microsoft.PowerShell.PSConsoleReadLine]::InvokePrompt()
+    }
+}
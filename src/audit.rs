@@ -0,0 +1,326 @@
+use crate::config::{AuditConfig, SessionConfig};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// One line of the audit log.
+///
+/// The log is only ever appended to, so the outcome of a command is a separate
+/// event pointing back at the generation it belongs to.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event {
+    Generated(Generated),
+    Outcome(OutcomeEvent),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Generated {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub session: String,
+    pub pwd: String,
+    pub prompt: String,
+    pub provider: String,
+    pub model: String,
+    pub command: String,
+    /// Warning returned along with the command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety: Option<String>,
+}
+
+impl Generated {
+    /// A generation that happened just now; the id is assigned when it is recorded
+    pub fn new(
+        session: String,
+        pwd: String,
+        prompt: String,
+        provider: String,
+        model: String,
+        command: String,
+    ) -> Self {
+        Self {
+            id: String::new(),
+            timestamp: Utc::now(),
+            session,
+            pwd,
+            prompt,
+            provider,
+            model,
+            command,
+            safety: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutcomeEvent {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub outcome: Outcome,
+    /// The command that actually ran, when it was edited first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// Run as generated
+    Executed,
+    /// Changed by the user, then run
+    Edited,
+    /// Replaced by another prompt or cleared without running
+    Discarded,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Executed => "executed",
+            Outcome::Edited => "edited",
+            Outcome::Discarded => "discarded",
+        }
+    }
+
+    pub fn accepted(self) -> bool {
+        matches!(self, Outcome::Executed | Outcome::Edited)
+    }
+}
+
+/// A generated command together with its reported outcome, if any
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    #[serde(flatten)]
+    pub generated: Generated,
+    pub outcome: Option<Outcome>,
+    pub executed_command: Option<String>,
+}
+
+pub fn default_path() -> PathBuf {
+    BaseDirs::new()
+        .map(|base_dirs| base_dirs.data_dir().join("chitin"))
+        .unwrap_or_else(|| std::env::temp_dir().join("chitin"))
+        .join("audit.jsonl")
+}
+
+pub fn path(config: &AuditConfig) -> PathBuf {
+    config
+        .file
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(default_path)
+}
+
+/// Daemon side of the audit log
+#[derive(Default)]
+pub struct AuditLog {
+    /// Latest generation per terminal session that has no outcome yet, and
+    /// when it was made.
+    ///
+    /// Keyed by the shell's session id rather than the history scope, so it
+    /// is trimmed on its own by `trim`.
+    pending: Mutex<HashMap<String, (String, Instant)>>,
+    sequence: AtomicU64,
+}
+
+impl AuditLog {
+    /// Append a generated command, returning its id
+    pub async fn record_generation(
+        &self,
+        config: &AuditConfig,
+        mut entry: Generated,
+    ) -> Result<Option<String>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        entry.id = format!("{:x}-{}", entry.timestamp.timestamp_micros(), sequence);
+        let id = entry.id.clone();
+        let session = entry.session.clone();
+
+        append_blocking(path(config), Event::Generated(entry)).await?;
+        self.pending
+            .lock()
+            .expect("audit lock")
+            .insert(session, (id.clone(), Instant::now()));
+        Ok(Some(id))
+    }
+
+    /// Stop waiting for outcomes from idle sessions, then from the least
+    /// recently active ones beyond `session.max_sessions`
+    pub fn trim(&self, session: &SessionConfig) {
+        let mut pending = self.pending.lock().expect("audit lock");
+        if session.ttl_secs > 0 {
+            let ttl = Duration::from_secs(session.ttl_secs);
+            pending.retain(|_, (_, at)| at.elapsed() <= ttl);
+        }
+
+        let excess = pending.len().saturating_sub(session.max_sessions.max(1));
+        if excess > 0 {
+            let mut by_age: Vec<(Instant, String)> = pending
+                .iter()
+                .map(|(session, (_, at))| (*at, session.clone()))
+                .collect();
+            by_age.sort();
+            for (_, session) in by_age.into_iter().take(excess) {
+                pending.remove(&session);
+            }
+        }
+    }
+
+    /// Append the outcome of the session's latest generated command.
+    ///
+    /// Returns the id of that generation, or `None` if there was nothing pending.
+    pub async fn record_outcome(
+        &self,
+        config: &AuditConfig,
+        session: &str,
+        outcome: Outcome,
+        command: Option<String>,
+    ) -> Result<Option<String>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let pending = self.pending.lock().expect("audit lock").remove(session);
+        let Some((id, _)) = pending else {
+            return Ok(None);
+        };
+        let event = OutcomeEvent {
+            id: id.clone(),
+            timestamp: Utc::now(),
+            outcome,
+            command: command.filter(|_| outcome == Outcome::Edited),
+        };
+        append_blocking(path(config), Event::Outcome(event)).await?;
+        Ok(Some(id))
+    }
+}
+
+/// `append` on the blocking pool, so a slow disk holds up no other requests
+async fn append_blocking(path: PathBuf, event: Event) -> Result<()> {
+    tokio::task::spawn_blocking(move || append(&path, &event))
+        .await
+        .context("audit log writer failed")?
+}
+
+fn append(path: &Path, event: &Event) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    let mut line = serde_json::to_vec(event)?;
+    line.push(b'\n');
+    // A single write in append mode keeps lines whole even with several writers
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    file.write_all(&line)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Read the audit log, joining each generation with its outcome, oldest first.
+///
+/// Lines that cannot be parsed (e.g. a write cut short by a crash) are skipped.
+pub fn read(path: &Path) -> Result<Vec<Record>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to open {}", path.display())),
+    };
+
+    let mut records = Vec::new();
+    let mut index = HashMap::new();
+    for line in BufReader::new(file).lines() {
+        let Ok(event) = serde_json::from_str::<Event>(&line?) else {
+            continue;
+        };
+        match event {
+            Event::Generated(generated) => {
+                index.insert(generated.id.clone(), records.len());
+                records.push(Record {
+                    generated,
+                    outcome: None,
+                    executed_command: None,
+                });
+            }
+            Event::Outcome(event) => {
+                if let Some(&i) = index.get(&event.id) {
+                    let record: &mut Record = &mut records[i];
+                    record.executed_command = match event.outcome {
+                        Outcome::Executed => Some(record.generated.command.clone()),
+                        Outcome::Edited => event.command,
+                        Outcome::Discarded => None,
+                    };
+                    record.outcome = Some(event.outcome);
+                }
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ContextScope;
+    use crate::session;
+
+    #[tokio::test]
+    async fn pending_outcomes_are_bounded_under_shared_scopes() {
+        let path =
+            std::env::temp_dir().join(format!("chitin-audit-test-{}.jsonl", std::process::id()));
+        let audit_config = AuditConfig {
+            enabled: true,
+            file: Some(path.to_string_lossy().into_owned()),
+        };
+        let session_config = SessionConfig {
+            max_sessions: 2,
+            scope: ContextScope::Project,
+            ..SessionConfig::default()
+        };
+
+        // Every terminal shares one history, but each waits for its own outcome
+        let log = AuditLog::default();
+        let terminals = ["a", "b", "c", "d"];
+        for terminal in terminals {
+            assert_eq!(
+                session::scope_key(session_config.scope, terminal, "/"),
+                session::scope_key(session_config.scope, "a", "/")
+            );
+            let entry = Generated::new(
+                terminal.to_string(),
+                "/".to_string(),
+                "list files".to_string(),
+                "noop".to_string(),
+                "none".to_string(),
+                "ls".to_string(),
+            );
+            log.record_generation(&audit_config, entry).await.unwrap();
+            log.trim(&session_config);
+        }
+
+        assert_eq!(log.pending.lock().unwrap().len(), 2);
+        for (terminal, pending) in [("a", false), ("b", false), ("c", true), ("d", true)] {
+            let id = log
+                .record_outcome(&audit_config, terminal, Outcome::Executed, None)
+                .await
+                .unwrap();
+            assert_eq!(id.is_some(), pending, "{terminal}");
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,9 +1,16 @@
-use crate::protocol::JsonRpcResponse;
+use crate::audit::Outcome;
+use crate::protocol::{
+    CommandOutput, JsonRpcResponse, METHOD_COMPLETE, METHOD_INPUT, METHOD_OUTCOME,
+    METHOD_SESSION_FORK, METHOD_SESSION_RESET, ResponseAction, SessionResult,
+};
+use crate::shell::Shell;
 use anyhow::{Result, anyhow};
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde_json::Value;
 use std::env;
-use std::io::Write;
-use std::path::Path;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
@@ -34,62 +41,62 @@ impl Drop for SpinnerGuard {
     }
 }
 
-pub async fn run(prompt: String, pwd: String) -> Result<()> {
-    // 1. Setup Spinner
-    let result: Result<Vec<u8>> = {
-        let _spinner = SpinnerGuard::new();
+/// Session of this terminal, as sent with every request
+pub fn session_id() -> String {
+    env::var("CHITIN_SESSION_ID")
+        .or_else(|_| env::var("USER"))
+        .unwrap_or_else(|_| "default".to_string())
+}
 
-        // 2. Connect to Socket
-        let config = crate::config::Config::load();
-        let socket_path = config.server.socket_path;
+/// Most captured output read from disk; the daemon trims it further
+const MAX_OUTPUT_BYTES: u64 = 64 * 1024;
 
-        if !Path::new(&socket_path).exists() {
-            return Err(anyhow!(
-                "Chitin daemon is not running (socket not found at {socket_path})"
-            ));
-        }
+/// Output of the previous command as captured by the shell plugin
+pub struct OutputCapture {
+    pub file: Option<PathBuf>,
+    pub exit_status: Option<i32>,
+}
 
-        let mut stream = UnixStream::connect(socket_path).await?;
-
-        // 3. Build Request
-        let session_id = env::var("CHITIN_SESSION_ID")
-            .or_else(|_| env::var("USER"))
-            .unwrap_or_else(|_| "default".to_string());
-
-        let request_id = get_time_id();
-
-        let payload = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "method": "chitin.input",
-            "params": {
-                "prompt": prompt,
-                "pwd": pwd,
-                "session_id": session_id
-            }
-        });
-
-        // 4. Send Request
-        let request_bytes = serde_json::to_vec(&payload)?;
-        stream.write_all(&request_bytes).await?;
-        stream.shutdown().await?;
-
-        // 5. Read Response
-        let mut response_bytes = Vec::new();
-        stream.read_to_end(&mut response_bytes).await?;
-
-        // _spinner is dropped here automatically at end of scope 'result', clearing logic
-        Ok(response_bytes)
-    };
+/// What the plugin tells about the shell the command is for
+pub struct ShellInfo {
+    pub shell: Option<Shell>,
+    pub version: Option<String>,
+    pub aliases: Vec<String>,
+}
 
-    // Process result after spinner is cleared
-    let response_bytes = result?;
+pub async fn run(
+    prompt: String,
+    pwd: String,
+    capture: OutputCapture,
+    shell: ShellInfo,
+) -> Result<()> {
+    let session_id = session_id();
+    // The daemon runs elsewhere, so relative paths mean nothing to it
+    let pwd = std::path::absolute(&pwd)?.to_string_lossy().into_owned();
+    let output = match capture.file {
+        Some(path) => read_output(&path)?.map(|text| CommandOutput {
+            text,
+            exit_status: capture.exit_status,
+        }),
+        None => None,
+    };
 
-    if response_bytes.is_empty() {
-        return Err(anyhow!("Empty response from daemon"));
-    }
+    let params = serde_json::json!({
+        "prompt": prompt,
+        "pwd": pwd,
+        "session_id": session_id,
+        "output": output,
+        "shell": shell.shell.map(Shell::as_str),
+        "shell_version": shell.version,
+        "path": env::var("PATH").ok(),
+        "aliases": shell.aliases,
+    });
 
-    let response: JsonRpcResponse = serde_json::from_slice(&response_bytes)?;
+    // The spinner is dropped at the end of this scope, before anything is printed
+    let response = {
+        let _spinner = SpinnerGuard::new();
+        call(METHOD_INPUT, params).await?
+    };
 
     if let Some(error) = response.error {
         eprintln!("Error: {}", error.message);
@@ -97,14 +104,177 @@ pub async fn run(prompt: String, pwd: String) -> Result<()> {
     }
 
     if let Some(result) = response.result {
+        let action: ResponseAction = serde_json::from_value(result)?;
+        if let Some(warning) = &action.warning {
+            eprintln!("Warning: {warning}");
+        }
         // Output result to stdout for capture by zsh
-        print!("{}", result.command);
+        print!("{}", action.command);
+        std::io::stdout().flush()?;
+    }
+
+    Ok(())
+}
+
+/// Print a suggestion for a prompt that is still being typed, or record one
+/// the user took with `accept`. No spinner: the plugin runs this in the
+/// background at every pause in typing.
+pub async fn complete(
+    prompt: String,
+    pwd: String,
+    shell: ShellInfo,
+    accept: Option<String>,
+) -> Result<()> {
+    let pwd = std::path::absolute(&pwd)?.to_string_lossy().into_owned();
+    let params = serde_json::json!({
+        "prompt": prompt,
+        "pwd": pwd,
+        "session_id": session_id(),
+        "shell": shell.shell.map(Shell::as_str),
+        "shell_version": shell.version,
+        "accepted": accept,
+    });
+    let response = call(METHOD_COMPLETE, params).await?;
+    if let Some(error) = response.error {
+        return Err(anyhow!(error.message));
+    }
+    if accept.is_none()
+        && let Some(result) = response.result
+    {
+        let action: ResponseAction = serde_json::from_value(result)?;
+        print!("{}", action.command);
         std::io::stdout().flush()?;
     }
+    Ok(())
+}
+
+/// Read the end of a capture file; a missing or empty file means no output
+fn read_output(path: &Path) -> Result<Option<String>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(anyhow!("failed to read {}: {err}", path.display())),
+    };
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(MAX_OUTPUT_BYTES)))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+    Ok((!text.trim().is_empty()).then_some(text))
+}
+
+/// Tell the daemon what became of the last command generated in this session
+pub async fn report_outcome(outcome: Outcome, command: Option<String>) -> Result<()> {
+    let params = serde_json::json!({
+        "session_id": session_id(),
+        "outcome": outcome,
+        "command": command,
+    });
+    let response = call(METHOD_OUTCOME, params).await?;
+    if let Some(error) = response.error {
+        return Err(anyhow!(error.message));
+    }
+    Ok(())
+}
+
+pub async fn reset_session() -> Result<()> {
+    let params = serde_json::json!({
+        "session_id": session_id(),
+        "pwd": env::current_dir()?,
+    });
+    let result = session_call(METHOD_SESSION_RESET, params).await?;
+    eprintln!("Session {} reset", result.session_id);
+    Ok(())
+}
 
+/// Copy a session and print the id of the copy, e.g. for `export CHITIN_SESSION_ID=$(...)`
+pub async fn fork_session(from: Option<String>, to: Option<String>) -> Result<()> {
+    let params = serde_json::json!({
+        "session_id": from.unwrap_or_else(session_id),
+        "new_session_id": to,
+    });
+    let result = session_call(METHOD_SESSION_FORK, params).await?;
+    eprintln!("Forked {} prompts", result.history);
+    println!("{}", result.session_id);
     Ok(())
 }
 
+async fn session_call(method: &str, params: Value) -> Result<SessionResult> {
+    let response = call(method, params).await?;
+    if let Some(error) = response.error {
+        return Err(anyhow!(error.message));
+    }
+    Ok(serde_json::from_value(response.result.unwrap_or_default())?)
+}
+
+/// Send a single JSON-RPC request to the daemon and wait for its response
+pub async fn call(method: &str, params: Value) -> Result<JsonRpcResponse> {
+    let config = crate::config::Config::load();
+    call_socket(config.server.socket_path, method, params).await
+}
+
+/// Like `call`, for a socket path the caller has already worked out
+pub async fn call_socket(
+    mut socket_path: String,
+    method: &str,
+    params: Value,
+) -> Result<JsonRpcResponse> {
+    // Daemons started outside a login session (e.g. system OpenRC services)
+    // have no runtime dir and listen on the /tmp fallback instead
+    let fallback = crate::socket::fallback_path();
+    if !Path::new(&socket_path).exists()
+        && Path::new(&socket_path) == crate::socket::default_path()
+        && fallback.exists()
+    {
+        socket_path = fallback.to_string_lossy().into_owned();
+    }
+
+    if !Path::new(&socket_path).exists() {
+        return Err(anyhow!(
+            "Chitin daemon is not running (socket not found at {socket_path})"
+        ));
+    }
+
+    let mut stream = UnixStream::connect(&socket_path).await?;
+
+    // Prompts and shell context must only go to our own daemon, not to
+    // whoever managed to bind the socket path first
+    let uid = crate::socket::current_uid();
+    match crate::socket::peer_uid(&stream) {
+        Some(peer) if peer == uid => {}
+        Some(peer) => {
+            return Err(anyhow!(
+                "Refusing to talk to {socket_path}: it is served by uid {peer}, not {uid}"
+            ));
+        }
+        None => {
+            return Err(anyhow!(
+                "Refusing to talk to {socket_path}: cannot tell who serves it"
+            ));
+        }
+    }
+
+    let payload = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": get_time_id(),
+        "method": method,
+        "params": params
+    });
+
+    let request_bytes = serde_json::to_vec(&payload)?;
+    stream.write_all(&request_bytes).await?;
+    stream.shutdown().await?;
+
+    let mut response_bytes = Vec::new();
+    stream.read_to_end(&mut response_bytes).await?;
+
+    if response_bytes.is_empty() {
+        return Err(anyhow!("Empty response from daemon"));
+    }
+
+    Ok(serde_json::from_slice(&response_bytes)?)
+}
+
 fn get_time_id() -> String {
     use std::time::SystemTime;
     let now = SystemTime::now()
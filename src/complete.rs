@@ -0,0 +1,171 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::oneshot;
+
+/// Suggestions for prompts that are still being typed.
+///
+/// These arrive at every pause in typing, so recent answers are kept for when
+/// the same prompt comes up again, and a newer request from a session cancels
+/// the one it replaces instead of waiting for the model on both.
+#[derive(Default)]
+pub struct Completions {
+    cache: Mutex<Cache>,
+    /// Request in flight per session, and how to cancel it
+    running: Mutex<HashMap<String, (u64, oneshot::Sender<()>)>>,
+    sequence: AtomicU64,
+}
+
+#[derive(Default)]
+struct Cache {
+    commands: HashMap<String, String>,
+    /// Keys from oldest to newest
+    order: VecDeque<String>,
+}
+
+/// Cache key of a prompt; the same words mean something else in another
+/// directory or shell
+pub fn key(shell: Option<&str>, pwd: &str, prompt: &str) -> String {
+    format!("{}\0{pwd}\0{prompt}", shell.unwrap_or_default())
+}
+
+impl Completions {
+    pub fn cached(&self, key: &str) -> Option<String> {
+        let cache = self.cache.lock().expect("completion cache lock");
+        cache.commands.get(key).cloned()
+    }
+
+    /// Remember a suggestion, dropping the oldest ones beyond `capacity`
+    pub fn store(&self, key: String, command: String, capacity: usize) {
+        let mut cache = self.cache.lock().expect("completion cache lock");
+        if cache.commands.insert(key.clone(), command).is_none() {
+            cache.order.push_back(key);
+        }
+        while cache.order.len() > capacity {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.commands.remove(&oldest);
+            }
+        }
+    }
+
+    /// Forget all suggestions, e.g. after switching to another model
+    pub fn clear(&self) {
+        let mut cache = self.cache.lock().expect("completion cache lock");
+        cache.commands.clear();
+        cache.order.clear();
+    }
+
+    /// Register a request from `session_id`, cancelling the one before it
+    pub fn start(&self, session_id: &str) -> Running<'_> {
+        let id = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let (cancel, cancelled) = oneshot::channel();
+        let previous = self
+            .running
+            .lock()
+            .expect("completion lock")
+            .insert(session_id.to_string(), (id, cancel));
+        if let Some((_, cancel)) = previous {
+            let _ = cancel.send(());
+        }
+        Running {
+            completions: self,
+            session_id: session_id.to_string(),
+            id,
+            cancelled,
+        }
+    }
+}
+
+/// A request in flight; it stops being the session's current one when dropped
+pub struct Running<'a> {
+    completions: &'a Completions,
+    session_id: String,
+    id: u64,
+    /// Resolves once a newer request from the same session arrives
+    pub cancelled: oneshot::Receiver<()>,
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        let mut running = self.completions.running.lock().expect("completion lock");
+        if running
+            .get(&self.session_id)
+            .is_some_and(|(id, _)| *id == self.id)
+        {
+            running.remove(&self.session_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot::error::TryRecvError;
+
+    #[test]
+    fn cached_by_shell_directory_and_prompt() {
+        let completions = Completions::default();
+        completions.store(key(Some("zsh"), "/tmp", "list files"), "ls".to_string(), 8);
+        assert_eq!(
+            completions
+                .cached(&key(Some("zsh"), "/tmp", "list files"))
+                .as_deref(),
+            Some("ls")
+        );
+        assert_eq!(
+            completions.cached(&key(Some("bash"), "/tmp", "list files")),
+            None
+        );
+        assert_eq!(
+            completions.cached(&key(Some("zsh"), "/", "list files")),
+            None
+        );
+
+        completions.clear();
+        assert_eq!(
+            completions.cached(&key(Some("zsh"), "/tmp", "list files")),
+            None
+        );
+    }
+
+    #[test]
+    fn oldest_dropped_at_capacity() {
+        let completions = Completions::default();
+        for prompt in ["one", "two", "three"] {
+            completions.store(prompt.to_string(), prompt.to_uppercase(), 2);
+        }
+        assert_eq!(completions.cached("one"), None);
+        assert_eq!(completions.cached("two").as_deref(), Some("TWO"));
+        assert_eq!(completions.cached("three").as_deref(), Some("THREE"));
+
+        // Storing a key again replaces it without taking a second slot
+        completions.store("three".to_string(), "3".to_string(), 2);
+        assert_eq!(completions.cached("two").as_deref(), Some("TWO"));
+        assert_eq!(completions.cached("three").as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn newer_request_cancels_the_older_one() {
+        let completions = Completions::default();
+        let mut first = completions.start("s");
+        let mut other = completions.start("other session");
+        assert_eq!(first.cancelled.try_recv(), Err(TryRecvError::Empty));
+
+        let mut second = completions.start("s");
+        assert_eq!(first.cancelled.try_recv(), Ok(()));
+        assert_eq!(second.cancelled.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(other.cancelled.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn finished_request_clears_its_slot() {
+        let completions = Completions::default();
+        let first = completions.start("s");
+        let second = completions.start("s");
+        // The replaced request must not clear its successor's slot
+        drop(first);
+        assert!(completions.running.lock().unwrap().contains_key("s"));
+        drop(second);
+        assert!(completions.running.lock().unwrap().is_empty());
+    }
+}
@@ -3,39 +3,277 @@ use std::env;
 use std::fs;
 use std::path::PathBuf;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct Config {
     #[serde(default)]
     pub server: ServerConfig,
     #[serde(default)]
     pub provider: ProviderConfig,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            server: ServerConfig::default(),
-            provider: ProviderConfig::default(),
-        }
-    }
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default)]
+    pub session: SessionConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub capture: CaptureConfig,
+    #[serde(default)]
+    pub complete: CompleteConfig,
+    /// Path of the config file this was loaded from, if any
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ServerConfig {
     #[serde(default = "default_socket_path")]
     pub socket_path: String,
+    /// Reload automatically when the config file changes
+    #[serde(default = "default_true")]
+    pub watch_config: bool,
+    /// Exit after this many seconds without requests when socket activated (0 disables)
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// How long shutdown waits for in-flight requests to finish
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             socket_path: default_socket_path(),
+            watch_config: true,
+            idle_timeout_secs: default_idle_timeout_secs(),
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
         }
     }
 }
 
+fn default_idle_timeout_secs() -> u64 {
+    900
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
 fn default_socket_path() -> String {
-    "/tmp/chitin.sock".to_string()
+    crate::socket::default_path().to_string_lossy().into_owned()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LogConfig {
+    /// Default filter, e.g. "info" or "chitin=debug"; RUST_LOG takes precedence
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    #[serde(default)]
+    pub format: LogFormat,
+    #[serde(default)]
+    pub destination: LogDestination,
+    /// Log file for the `file` destination, defaults to a per-user log directory
+    pub file: Option<String>,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Rotated log files to keep (0 keeps all of them)
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+    /// Include prompts and generated commands in the log
+    #[serde(default)]
+    pub log_prompts: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            format: LogFormat::default(),
+            destination: LogDestination::default(),
+            file: None,
+            rotation: LogRotation::default(),
+            max_files: default_log_max_files(),
+            log_prompts: false,
+        }
+    }
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    Compact,
+    Json,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogDestination {
+    #[default]
+    Stderr,
+    File,
+    Journald,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SessionConfig {
+    /// Forget a session after this many seconds without requests (0 keeps it)
+    #[serde(default = "default_session_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Most sessions kept in memory; the least recently used go first
+    #[serde(default = "default_max_sessions")]
+    pub max_sessions: usize,
+    /// Prompts per session sent along as context
+    #[serde(default = "default_max_history")]
+    pub max_history: usize,
+    /// What the history used as context is shared by
+    #[serde(default)]
+    pub scope: ContextScope,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextScope {
+    /// Each terminal session has its own history
+    #[default]
+    Session,
+    /// Shared by everything under the same git repository (or directory outside one)
+    Project,
+    /// Shared by everything run in the same directory
+    Directory,
+    /// One history for everything
+    Global,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_session_ttl_secs(),
+            max_sessions: default_max_sessions(),
+            max_history: default_max_history(),
+            scope: ContextScope::default(),
+        }
+    }
+}
+
+fn default_session_ttl_secs() -> u64 {
+    4 * 60 * 60
+}
+
+fn default_max_sessions() -> usize {
+    256
+}
+
+fn default_max_history() -> usize {
+    10
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuditConfig {
+    /// Record every generated command and its outcome
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Audit log location, defaults to the per-user data directory
+    pub file: Option<String>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            file: None,
+        }
+    }
+}
+
+/// Handling of command output sent by the shell plugin (which only captures
+/// when enabled there)
+#[derive(Debug, Deserialize, Clone)]
+pub struct CaptureConfig {
+    /// Use captured output as context; when false it is dropped on arrival
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Keep at most this many bytes from the end of the output (0 sends none)
+    #[serde(default = "default_capture_max_bytes")]
+    pub max_bytes: usize,
+    /// Redact common secrets such as API keys, tokens and private keys
+    #[serde(default = "default_true")]
+    pub redact_defaults: bool,
+    /// Extra regular expressions whose matches are replaced with [REDACTED]
+    #[serde(default)]
+    pub redact: Vec<String>,
+    /// `redact`, compiled when the config is loaded
+    #[serde(skip)]
+    pub redact_patterns: Vec<regex::Regex>,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_bytes: default_capture_max_bytes(),
+            redact_defaults: true,
+            redact: Vec::new(),
+            redact_patterns: Vec::new(),
+        }
+    }
+}
+
+fn default_capture_max_bytes() -> usize {
+    8 * 1024
+}
+
+/// Suggestions for prompts that are still being typed (`chitin.complete`)
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompleteConfig {
+    /// Answer suggestion requests; when false they fail right away
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Give up on a suggestion that takes longer than this
+    #[serde(default = "default_complete_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Suggestions kept for prompts typed again (0 disables the cache)
+    #[serde(default = "default_complete_cache_size")]
+    pub cache_size: usize,
+}
+
+impl Default for CompleteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_ms: default_complete_timeout_ms(),
+            cache_size: default_complete_cache_size(),
+        }
+    }
+}
+
+fn default_complete_timeout_ms() -> u64 {
+    3000
+}
+
+fn default_complete_cache_size() -> usize {
+    256
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -76,15 +314,34 @@ impl Config {
         })
     }
 
+    /// The daemon's socket path, read even from a config file that fails to
+    /// load, for commands that leave reporting such errors to the daemon
+    pub fn socket_path() -> String {
+        let from_file = get_config_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| toml::from_str::<toml::Table>(&content).ok())
+            .and_then(|table| {
+                let path = table.get("server")?.get("socket_path")?.as_str()?;
+                Some(path.to_string())
+            });
+        env::var("CHITIN_SOCKET_PATH")
+            .ok()
+            .or(from_file)
+            .unwrap_or_else(default_socket_path)
+    }
+
     pub fn load_reload() -> Result<Self, String> {
         let config_path = get_config_path();
 
         let mut config = if let Some(path) = &config_path {
             if path.exists() {
-                match fs::read_to_string(path) {
-                    Ok(content) => toml::from_str(&content).map_err(|e| e.to_string())?,
-                    Err(e) => return Err(e.to_string()),
-                }
+                let mut config: Config = match fs::read_to_string(path) {
+                    Ok(content) => toml::from_str(&content)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?,
+                    Err(e) => return Err(format!("{}: {}", path.display(), e)),
+                };
+                config.source = Some(path.clone());
+                config
             } else {
                 Config::default()
             }
@@ -94,43 +351,182 @@ impl Config {
 
         // Environment variables override config file
         merge_env_vars(&mut config);
+        crate::capture::compile(&mut config.capture)?;
         Ok(config)
     }
 }
 
-fn get_config_path() -> Option<PathBuf> {
-    // 1. Environment variable
+/// Push "name: old -> new" onto `changes` for each listed field that differs
+macro_rules! compare {
+    ($changes:ident, $old:ident, $new:ident; $($name:literal => $($field:ident).+),+ $(,)?) => {
+        $(
+            let (old, new) = ($old.$($field).+.show(), $new.$($field).+.show());
+            if old != new {
+                $changes.push(format!(
+                    "{}: {} -> {}",
+                    $name,
+                    old.as_deref().unwrap_or("(unset)"),
+                    new.as_deref().unwrap_or("(unset)")
+                ));
+            }
+        )+
+    };
+}
+
+impl Config {
+    /// Files whose changes should trigger a reload: the one loaded and every
+    /// place that would take precedence over it, or all of them when no
+    /// config file exists yet
+    pub fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut candidates = config_candidates();
+        if let Some(source) = &self.source {
+            match candidates.iter().position(|path| path == source) {
+                Some(index) => candidates.truncate(index + 1),
+                None => candidates.push(source.clone()),
+            }
+        }
+        candidates
+    }
+
+    /// Human readable list of settings that differ from `other`, with secrets masked
+    pub fn diff(&self, other: &Config) -> Vec<String> {
+        let mut changes = Vec::new();
+        compare!(changes, self, other;
+            "config file" => source,
+            "server.socket_path" => server.socket_path,
+            "server.watch_config" => server.watch_config,
+            "server.idle_timeout_secs" => server.idle_timeout_secs,
+            "server.shutdown_timeout_secs" => server.shutdown_timeout_secs,
+            "log.level" => log.level,
+            "log.format" => log.format,
+            "log.destination" => log.destination,
+            "log.file" => log.file,
+            "log.rotation" => log.rotation,
+            "log.max_files" => log.max_files,
+            "log.log_prompts" => log.log_prompts,
+            "session.ttl_secs" => session.ttl_secs,
+            "session.max_sessions" => session.max_sessions,
+            "session.max_history" => session.max_history,
+            "session.scope" => session.scope,
+            "audit.enabled" => audit.enabled,
+            "audit.file" => audit.file,
+            "capture.enabled" => capture.enabled,
+            "capture.max_bytes" => capture.max_bytes,
+            "capture.redact_defaults" => capture.redact_defaults,
+            "capture.redact" => capture.redact,
+            "complete.enabled" => complete.enabled,
+            "complete.timeout_ms" => complete.timeout_ms,
+            "complete.cache_size" => complete.cache_size,
+            "provider.type" => provider.type_,
+            "provider.openai.api_base" => provider.openai.api_base,
+            "provider.openai.model" => provider.openai.model,
+        );
+
+        let (old, new) = (&self.provider.openai, &other.provider.openai);
+        if old.api_key != new.api_key {
+            let masked = |key: &Option<String>| {
+                key.as_deref()
+                    .map(mask_secret)
+                    .unwrap_or_else(|| "(unset)".to_string())
+            };
+            changes.push(format!(
+                "provider.openai.api_key: {} -> {}",
+                masked(&old.api_key),
+                masked(&new.api_key)
+            ));
+        }
+
+        changes
+    }
+}
+
+/// How a setting is shown by `Config::diff`; `None` when it is not set
+trait Setting {
+    fn show(&self) -> Option<String>;
+}
+
+macro_rules! display_setting {
+    ($($type:ty),+) => {
+        $(impl Setting for $type {
+            fn show(&self) -> Option<String> {
+                Some(self.to_string())
+            }
+        })+
+    };
+}
+display_setting!(bool, u64, usize, String);
+
+macro_rules! debug_setting {
+    ($($type:ty),+) => {
+        $(impl Setting for $type {
+            fn show(&self) -> Option<String> {
+                Some(format!("{self:?}"))
+            }
+        })+
+    };
+}
+debug_setting!(LogFormat, LogDestination, LogRotation, ContextScope);
+
+impl<T: Setting> Setting for Option<T> {
+    fn show(&self) -> Option<String> {
+        self.as_ref().and_then(Setting::show)
+    }
+}
+
+impl Setting for PathBuf {
+    fn show(&self) -> Option<String> {
+        Some(self.display().to_string())
+    }
+}
+
+impl Setting for Vec<String> {
+    fn show(&self) -> Option<String> {
+        Some(self.join(", "))
+    }
+}
+
+/// Where a config file is looked for, most preferred first
+fn config_candidates() -> Vec<PathBuf> {
+    // 1. Environment variable, the only place looked at when set
     if let Ok(path) = env::var("CHITIN_CONFIG") {
-        return Some(PathBuf::from(path));
+        return vec![PathBuf::from(path)];
     }
 
     // 2. Current directory
-    let current_dir_config = PathBuf::from("chitin.toml");
-    if current_dir_config.exists() {
-        return Some(current_dir_config);
-    }
+    let mut candidates = vec![PathBuf::from("chitin.toml")];
 
     // 3. ~/.config/chitin/config.toml (Explicit XDG-style support)
     if let Some(base_dirs) = directories::BaseDirs::new() {
-        let xdg_config = base_dirs
-            .home_dir()
-            .join(".config")
-            .join("chitin")
-            .join("config.toml");
-        if xdg_config.exists() {
-            return Some(xdg_config);
-        }
+        candidates.push(
+            base_dirs
+                .home_dir()
+                .join(".config")
+                .join("chitin")
+                .join("config.toml"),
+        );
     }
 
     // 4. System default (Platform specific)
     if let Some(proj_dirs) = directories::ProjectDirs::from("com", "user", "chitin") {
-        let sys_config = proj_dirs.config_dir().join("config.toml");
-        if sys_config.exists() {
-            return Some(sys_config);
-        }
+        candidates.push(proj_dirs.config_dir().join("config.toml"));
     }
 
-    None
+    candidates.dedup();
+    candidates
+}
+
+fn get_config_path() -> Option<PathBuf> {
+    config_candidates().into_iter().find(|path| path.exists())
+}
+
+/// Mask a secret for display, keeping only the last few characters
+pub fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() < 12 {
+        return "****".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{tail}")
 }
 
 fn merge_env_vars(config: &mut Config) {
@@ -150,3 +546,29 @@ fn merge_env_vars(config: &mut Config) {
         config.provider.openai.model = Some(val);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_lists_changed_settings() {
+        let old = Config::default();
+        let mut new = Config::default();
+        new.server.watch_config = false;
+        new.session.scope = ContextScope::Project;
+        new.log.file = Some("/tmp/chitin.log".to_string());
+        new.provider.openai.api_key = Some("sk-0123456789abcdef".to_string());
+
+        assert_eq!(
+            old.diff(&new),
+            [
+                "server.watch_config: true -> false",
+                "log.file: (unset) -> /tmp/chitin.log",
+                "session.scope: Session -> Project",
+                "provider.openai.api_key: (unset) -> ****cdef",
+            ]
+        );
+        assert!(new.diff(&new).is_empty());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::audit::{self, Record};
+use crate::client;
+use crate::config::Config;
+use crate::protocol::METHOD_HISTORY;
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Records returned when the filter sets no limit
+pub const DEFAULT_LIMIT: usize = 1000;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum ExportFormat {
+    /// Human readable listing
+    #[default]
+    Text,
+    Jsonl,
+    Csv,
+    /// Distinct generated commands only, best match first (for shell widgets)
+    Commands,
+}
+
+/// Which audit records `chitin history` shows; also the `chitin.history` params
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Filter {
+    /// Fuzzy search over prompts and commands
+    pub query: Option<String>,
+    /// Only this session
+    pub session: Option<String>,
+    /// First day to include, in local time
+    pub since: Option<NaiveDate>,
+    /// Last day to include, in local time
+    pub until: Option<NaiveDate>,
+    /// Only commands generated in this directory or below it
+    pub dir: Option<PathBuf>,
+    /// Only commands that were run, as generated or after editing
+    pub accepted: bool,
+    /// Keep only the most recent (or best matching) records; `DEFAULT_LIMIT`
+    /// when unset and all of them when 0
+    pub limit: Option<usize>,
+}
+
+impl Filter {
+    fn matches(&self, record: &Record) -> bool {
+        let day = record
+            .generated
+            .timestamp
+            .with_timezone(&Local)
+            .date_naive();
+        if self.since.is_some_and(|since| day < since)
+            || self.until.is_some_and(|until| day > until)
+        {
+            return false;
+        }
+        if let Some(dir) = &self.dir
+            && !Path::new(&record.generated.pwd).starts_with(dir)
+        {
+            return false;
+        }
+        if let Some(session) = &self.session
+            && &record.generated.session != session
+        {
+            return false;
+        }
+        !self.accepted || record.outcome.is_some_and(|outcome| outcome.accepted())
+    }
+}
+
+/// Apply `filter` to audit records in log order.
+///
+/// Returns the newest record first, or the best match first when searching.
+pub fn select(records: Vec<Record>, filter: &Filter) -> Vec<Record> {
+    let mut records: Vec<Record> = records
+        .into_iter()
+        .rev()
+        .filter(|record| filter.matches(record))
+        .collect();
+
+    if let Some(query) = filter.query.as_deref().filter(|q| !q.trim().is_empty()) {
+        let mut scored: Vec<(i64, Record)> = records
+            .into_iter()
+            .filter_map(|record| {
+                let prompt = fuzzy_score(query, &record.generated.prompt);
+                let command = fuzzy_score(query, &record.generated.command);
+                prompt.max(command).map(|score| (score, record))
+            })
+            .collect();
+        // Stable, so equally good matches stay newest first
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        records = scored.into_iter().map(|(_, record)| record).collect();
+    }
+
+    match filter.limit.unwrap_or(DEFAULT_LIMIT) {
+        0 => {}
+        limit => records.truncate(limit),
+    }
+    records
+}
+
+/// Score how well `needle` matches `haystack` as a case-insensitive subsequence.
+///
+/// Consecutive characters and matches at the start of words score higher;
+/// `None` means not every character of the needle was found.
+fn fuzzy_score(needle: &str, haystack: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut last_match: Option<usize> = None;
+    let mut chars = haystack.char_indices().peekable();
+    let mut previous = ' ';
+
+    for wanted in needle.chars().filter(|c| !c.is_whitespace()) {
+        let wanted = wanted.to_lowercase().next().unwrap_or(wanted);
+        loop {
+            let (index, c) = chars.next()?;
+            let word_start = !previous.is_alphanumeric();
+            previous = c;
+            if c.to_lowercase().next() != Some(wanted) {
+                continue;
+            }
+            score += 1;
+            if word_start {
+                score += 8;
+            }
+            match last_match {
+                Some(last) if index == last + 1 => score += 5,
+                Some(last) => score -= ((index - last) as i64).min(5),
+                None => {}
+            }
+            last_match = Some(index + c.len_utf8() - 1);
+            break;
+        }
+    }
+    Some(score)
+}
+
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("expected YYYY-MM-DD, got {value:?}"))
+}
+
+pub async fn run(mut filter: Filter, format: ExportFormat) -> Result<()> {
+    if let Some(dir) = &filter.dir {
+        filter.dir = Some(
+            std::path::absolute(dir)
+                .with_context(|| format!("invalid directory {}", dir.display()))?,
+        );
+    }
+    if let (Some(since), Some(until)) = (filter.since, filter.until)
+        && since > until
+    {
+        bail!("--since {since} is after --until {until}");
+    }
+
+    // Ask the daemon, which knows the configured audit log; read it directly
+    // when no daemon is running
+    let records = match client::call(METHOD_HISTORY, serde_json::to_value(&filter)?).await {
+        Ok(response) => {
+            if let Some(error) = response.error {
+                return Err(anyhow!(error.message));
+            }
+            serde_json::from_value(response.result.unwrap_or_default())?
+        }
+        Err(_) => {
+            let config = Config::load();
+            select(audit::read(&audit::path(&config.audit))?, &filter)
+        }
+    };
+
+    let mut out = std::io::stdout().lock();
+    match format {
+        ExportFormat::Text => {
+            if records.is_empty() {
+                writeln!(out, "No matching history")?;
+            }
+            // Oldest first, so the newest or best match ends up next to the prompt
+            for record in records.iter().rev() {
+                write_text(&mut out, record)?;
+            }
+        }
+        ExportFormat::Jsonl => {
+            for record in records.iter().rev() {
+                serde_json::to_writer(&mut out, record)?;
+                writeln!(out)?;
+            }
+        }
+        ExportFormat::Csv => {
+            writeln!(
+                out,
+                "id,timestamp,session,pwd,prompt,provider,model,command,safety,outcome,executed_command"
+            )?;
+            for record in records.iter().rev() {
+                let generated = &record.generated;
+                let fields = [
+                    generated.id.as_str(),
+                    &generated.timestamp.to_rfc3339(),
+                    &generated.session,
+                    &generated.pwd,
+                    &generated.prompt,
+                    &generated.provider,
+                    &generated.model,
+                    &generated.command,
+                    generated.safety.as_deref().unwrap_or(""),
+                    record.outcome.map(|o| o.as_str()).unwrap_or(""),
+                    record.executed_command.as_deref().unwrap_or(""),
+                ];
+                let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                writeln!(out, "{}", row.join(","))?;
+            }
+        }
+        ExportFormat::Commands => {
+            let mut seen = HashSet::new();
+            for record in &records {
+                let command = &record.generated.command;
+                if seen.insert(command) {
+                    writeln!(out, "{command}")?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_text(out: &mut impl Write, record: &Record) -> Result<()> {
+    let generated = &record.generated;
+    let time = generated.timestamp.with_timezone(&Local);
+    let outcome = record.outcome.map(|o| o.as_str()).unwrap_or("-");
+    writeln!(
+        out,
+        "{}  {:<9}  {}  [{}]",
+        time.format("%Y-%m-%d %H:%M:%S"),
+        outcome,
+        generated.pwd,
+        generated.session
+    )?;
+    writeln!(out, "  {}", generated.prompt)?;
+    writeln!(out, "  $ {}", generated.command)?;
+    if let Some(executed) = &record.executed_command
+        && executed != &generated.command
+    {
+        writeln!(out, "  ran: {executed}")?;
+    }
+    Ok(())
+}
+
+/// Quote a CSV field when needed (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{Generated, Outcome};
+    use chrono::{TimeZone, Utc};
+
+    /// A record generated at noon local time on `day` of January 2026
+    fn record(day: u32, session: &str, pwd: &str, prompt: &str, command: &str) -> Record {
+        let mut generated = Generated::new(
+            session.to_string(),
+            pwd.to_string(),
+            prompt.to_string(),
+            "noop".to_string(),
+            "none".to_string(),
+            command.to_string(),
+        );
+        generated.id = format!("{day}-{command}");
+        generated.timestamp = Local
+            .with_ymd_and_hms(2026, 1, day, 12, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        Record {
+            generated,
+            outcome: None,
+            executed_command: None,
+        }
+    }
+
+    fn commands(records: &[Record]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|record| record.generated.command.as_str())
+            .collect()
+    }
+
+    fn date(day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(2026, 1, day)
+    }
+
+    #[test]
+    fn newest_first_and_limited() {
+        let records = || {
+            (0..DEFAULT_LIMIT + 5)
+                .map(|i| record(1, "s", "/", "p", &format!("echo {i}")))
+                .collect::<Vec<_>>()
+        };
+
+        let selected = select(records(), &Filter::default());
+        assert_eq!(selected.len(), DEFAULT_LIMIT);
+        assert_eq!(
+            selected[0].generated.command,
+            format!("echo {}", DEFAULT_LIMIT + 4)
+        );
+        assert_eq!(selected[DEFAULT_LIMIT - 1].generated.command, "echo 5");
+
+        let all = Filter {
+            limit: Some(0),
+            ..Filter::default()
+        };
+        assert_eq!(select(records(), &all).len(), DEFAULT_LIMIT + 5);
+
+        let three = Filter {
+            limit: Some(3),
+            ..Filter::default()
+        };
+        assert_eq!(
+            commands(&select(records(), &three)),
+            [
+                format!("echo {}", DEFAULT_LIMIT + 4),
+                format!("echo {}", DEFAULT_LIMIT + 3),
+                format!("echo {}", DEFAULT_LIMIT + 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let mut executed = record(3, "b", "/home/me/project/src", "p", "executed");
+        executed.outcome = Some(Outcome::Executed);
+        let mut edited = record(4, "a", "/home/me/projects", "p", "edited");
+        edited.outcome = Some(Outcome::Edited);
+        let mut discarded = record(5, "a", "/home/me/project", "p", "discarded");
+        discarded.outcome = Some(Outcome::Discarded);
+        let records = || {
+            vec![
+                record(1, "a", "/home/me", "p", "first"),
+                record(2, "a", "/home/me/project", "p", "second"),
+                executed.clone(),
+                edited.clone(),
+                discarded.clone(),
+            ]
+        };
+        let selected = |filter: Filter| commands(&select(records(), &filter)).join(" ");
+
+        assert_eq!(
+            selected(Filter {
+                since: date(2),
+                until: date(4),
+                ..Filter::default()
+            }),
+            "edited executed second"
+        );
+        assert_eq!(
+            selected(Filter {
+                dir: Some(PathBuf::from("/home/me/project")),
+                ..Filter::default()
+            }),
+            "discarded executed second"
+        );
+        assert_eq!(
+            selected(Filter {
+                session: Some("b".to_string()),
+                ..Filter::default()
+            }),
+            "executed"
+        );
+        assert_eq!(
+            selected(Filter {
+                accepted: true,
+                ..Filter::default()
+            }),
+            "edited executed"
+        );
+    }
+
+    #[test]
+    fn best_match_first() {
+        let records = || {
+            vec![
+                record(1, "s", "/", "show the repository state", "git status"),
+                record(
+                    2,
+                    "s",
+                    "/",
+                    "search for the word status",
+                    "grep -r status .",
+                ),
+                record(3, "s", "/", "list files", "ls -la"),
+                record(4, "s", "/", "what changed", "git diff"),
+            ]
+        };
+        let search = |query: &str, limit| {
+            let filter = Filter {
+                query: Some(query.to_string()),
+                limit,
+                ..Filter::default()
+            };
+            select(records(), &filter)
+        };
+
+        // Runs of characters at word starts beat scattered matches, however old
+        assert_eq!(
+            commands(&search("gst", None)),
+            ["git status", "grep -r status ."]
+        );
+        assert_eq!(commands(&search("GIT S", Some(1))), ["git status"]);
+        // Prompts are searched too
+        assert_eq!(commands(&search("list", None)), ["ls -la"]);
+        // Equally good matches stay newest first
+        assert_eq!(commands(&search("git", None)), ["git diff", "git status"]);
+        assert!(search("xyz", None).is_empty());
+        // An empty query only filters
+        assert_eq!(search(" ", None).len(), 4);
+    }
+
+    #[test]
+    fn fuzzy_scores() {
+        assert_eq!(fuzzy_score("gz", "git status"), None);
+        assert!(fuzzy_score("gs", "git status") > fuzzy_score("gs", "bugs"));
+        assert!(fuzzy_score("stat", "git status") > fuzzy_score("stat", "sxtxaxt"));
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::client;
+use crate::protocol::METHOD_STATUS;
+use crate::socket;
+use anyhow::{Context, Result, bail};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use tokio::time::{Duration, Instant, sleep, timeout};
+use tracing::info;
+
+/// How long `--replace` waits for the previous daemon to shut down
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Second line of the PID file of a daemon started by socket activation
+const ACTIVATED_MARKER: &str = "socket-activated";
+
+pub fn pid_path() -> PathBuf {
+    socket::runtime_dir().join("chitin.pid")
+}
+
+/// Exclusive lock on the PID file, held for the lifetime of the daemon.
+///
+/// The file is left behind on exit: removing it while still locked would let
+/// a `--replace` successor waiting on the old inode lock a file nobody else
+/// can see. Without the lock its pid counts as stale.
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// Make sure this is the only daemon for the current user.
+///
+/// A running daemon is detected either by its lock on the PID file or by a
+/// live socket. With `replace`, it is asked to shut down and we take over once
+/// it has exited; otherwise this fails with a message naming the running pid.
+/// A socket activated daemon is not replaced, since its service manager would
+/// start it again or keep holding the socket.
+///
+/// `socket_path` is `None` when the socket was inherited from the service
+/// manager, which keeps it listening even with no daemon behind it.
+pub async fn acquire(socket_path: Option<&Path>, replace: bool) -> Result<InstanceLock> {
+    let path = pid_path();
+    if let Some(dir) = path.parent() {
+        socket::ensure_private_dir(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+
+    let socket_live = || socket_path.is_some_and(socket::is_live);
+    let locked = try_lock(&file);
+    if !locked || socket_live() {
+        let owner = if locked {
+            None
+        } else {
+            read_pid_file(&mut file)
+        };
+        if replace && let Some((pid, true)) = owner {
+            bail!(
+                "chitin daemon (pid {pid}) was started by the service manager, which would \
+                 start it again or keep its socket. Use `chitin service stop` instead."
+            );
+        }
+        let pid = match owner {
+            Some((pid, _)) => Some(pid),
+            None => query_pid().await,
+        };
+        let describe = pid
+            .map(|pid| format!("pid {pid}"))
+            .unwrap_or_else(|| "unknown pid".to_string());
+
+        if !replace {
+            bail!(
+                "chitin daemon is already running ({describe}). \
+                 Use `chitin daemon --replace` to take over."
+            );
+        }
+        let Some(pid) = pid else {
+            bail!("a chitin daemon is running but its pid could not be determined");
+        };
+
+        info!("Chitin: asking running daemon ({describe}) to shut down...");
+        signal(pid, libc::SIGTERM)?;
+
+        let deadline = Instant::now() + REPLACE_TIMEOUT;
+        while !try_lock(&file) || socket_live() {
+            if Instant::now() >= deadline {
+                bail!("chitin daemon ({describe}) did not exit within {REPLACE_TIMEOUT:?}");
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}", std::process::id())?;
+    if socket_path.is_none() {
+        writeln!(file, "{ACTIVATED_MARKER}")?;
+    }
+    file.flush()?;
+
+    Ok(InstanceLock { _file: file })
+}
+
+/// Pid of the daemon holding the PID file lock, if one is running
+pub fn running_pid() -> Option<i32> {
+    let mut file = File::open(pid_path()).ok()?;
+    if try_lock(&file) {
+        // Nobody holds the lock, so the pid in the file is stale
+        return None;
+    }
+    read_pid(&mut file)
+}
+
+pub fn signal(pid: i32, signal: libc::c_int) -> Result<()> {
+    // SAFETY: kill only reads its integer arguments
+    if unsafe { libc::kill(pid, signal) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("failed to signal pid {pid}"));
+    }
+    Ok(())
+}
+
+fn try_lock(file: &File) -> bool {
+    // SAFETY: the descriptor stays valid for as long as `file` is borrowed
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+}
+
+fn read_pid(file: &mut File) -> Option<i32> {
+    read_pid_file(file).map(|(pid, _)| pid)
+}
+
+/// The pid in the PID file, and whether that daemon was socket activated
+fn read_pid_file(file: &mut File) -> Option<(i32, bool)> {
+    let mut content = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut content).ok()?;
+    let mut lines = content.lines();
+    let pid = lines.next()?.trim().parse().ok()?;
+    Some((pid, lines.next() == Some(ACTIVATED_MARKER)))
+}
+
+/// Ask a daemon that predates the PID file for its pid over the socket
+async fn query_pid() -> Option<i32> {
+    let call = client::call(METHOD_STATUS, serde_json::json!({}));
+    let response = timeout(Duration::from_secs(1), call).await.ok()?.ok()?;
+    response.result?.get("pid")?.as_i64()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pid_file_records_socket_activation() {
+        let path = std::env::temp_dir().join(format!("chitin-pid-{}", std::process::id()));
+        for (content, expected) in [
+            ("1234\n", Some((1234, false))),
+            ("1234\nsocket-activated\n", Some((1234, true))),
+            ("", None),
+            ("not a pid\n", None),
+        ] {
+            std::fs::write(&path, content).unwrap();
+            let mut file = File::open(&path).unwrap();
+            assert_eq!(read_pid_file(&mut file), expected, "{content:?}");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
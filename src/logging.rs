@@ -0,0 +1,149 @@
+use crate::config::{LogConfig, LogDestination, LogFormat, LogRotation};
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use tracing::Subscriber;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Keeps the background log file writer alive; dropping it flushes pending lines
+pub struct LogGuard {
+    _worker: Option<WorkerGuard>,
+}
+
+/// Install the global subscriber for the daemon as described by `[log]`
+pub fn init(config: &LogConfig) -> Result<LogGuard> {
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => EnvFilter::try_new(&config.level)
+            .with_context(|| format!("invalid log.level {:?}", config.level))?,
+    };
+    let registry = tracing_subscriber::registry().with(filter);
+
+    match config.destination {
+        LogDestination::Stderr => {
+            let ansi = std::io::stderr().is_terminal();
+            registry
+                .with(fmt_layer(config.format, ansi, std::io::stderr))
+                .init();
+            Ok(LogGuard { _worker: None })
+        }
+        LogDestination::File => {
+            let path = file_path(config);
+            let (dir, prefix, suffix) = split_file_path(&path);
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+            let mut builder = RollingFileAppender::builder()
+                .rotation(match config.rotation {
+                    LogRotation::Hourly => Rotation::HOURLY,
+                    LogRotation::Daily => Rotation::DAILY,
+                    LogRotation::Never => Rotation::NEVER,
+                })
+                .filename_prefix(prefix)
+                .filename_suffix(suffix);
+            if config.max_files > 0 {
+                builder = builder.max_log_files(config.max_files);
+            }
+            let appender = builder
+                .build(&dir)
+                .with_context(|| format!("failed to open log file in {}", dir.display()))?;
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            registry
+                .with(fmt_layer(config.format, false, writer))
+                .init();
+            Ok(LogGuard {
+                _worker: Some(guard),
+            })
+        }
+        LogDestination::Journald => {
+            // journald keeps fields structured, so `format` does not apply
+            let layer = tracing_journald::layer()
+                .context("failed to connect to journald")?
+                .with_syslog_identifier("chitin".to_string());
+            registry.with(layer).init();
+            Ok(LogGuard { _worker: None })
+        }
+    }
+}
+
+fn fmt_layer<S, W>(format: LogFormat, ansi: bool, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .with_ansi(ansi)
+        .with_writer(writer);
+    match format {
+        LogFormat::Pretty => Box::new(layer.pretty()),
+        LogFormat::Compact => Box::new(layer.compact()),
+        LogFormat::Json => Box::new(layer.json()),
+    }
+}
+
+/// Per-user directory for log files
+pub fn default_dir() -> PathBuf {
+    let Some(base_dirs) = BaseDirs::new() else {
+        return std::env::temp_dir().join("chitin");
+    };
+    if cfg!(target_os = "macos") {
+        return base_dirs.home_dir().join("Library/Logs/chitin");
+    }
+    base_dirs
+        .state_dir()
+        .unwrap_or(base_dirs.data_dir())
+        .join("chitin")
+}
+
+/// Configured log file, before the rotation date is added to its name
+pub fn file_path(config: &LogConfig) -> PathBuf {
+    config
+        .file
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default_dir().join("chitin.log"))
+}
+
+/// The log file currently being written to, which carries a date when rotated
+pub fn current_file(config: &LogConfig) -> Option<PathBuf> {
+    let path = file_path(config);
+    if config.rotation == LogRotation::Never {
+        return path.exists().then_some(path);
+    }
+    let (dir, prefix, suffix) = split_file_path(&path);
+    std::fs::read_dir(&dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with(prefix.as_str()) && name.ends_with(suffix.as_str())
+        })
+        .max_by_key(|entry| entry.metadata().and_then(|m| m.modified()).ok())
+        .map(|entry| entry.path())
+}
+
+/// Split `dir/chitin.log` into the directory, "chitin" and "log"
+fn split_file_path(path: &Path) -> (PathBuf, String, String) {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let prefix = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "chitin".to_string());
+    let suffix = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    (dir, prefix, suffix)
+}
@@ -1,27 +1,49 @@
+mod audit;
+mod capture;
 mod client;
+mod complete;
 mod config;
+mod history;
+mod instance;
+mod logging;
 mod protocol;
 mod provider;
+mod reload;
 mod session;
 mod shell;
+mod socket;
+mod status;
+mod validate;
+mod watch;
 
 use anyhow::Result;
+use audit::{AuditLog, Generated};
+use chrono::NaiveDate;
 use clap::{Parser, Subcommand};
+use config::ContextScope;
 use protocol::{
-    JsonRpcRequest, JsonRpcResponse, ResponseAction, internal_error, invalid_params,
-    invalid_request, method_not_found,
+    CompleteParams, InputParams, JsonRpcRequest, JsonRpcResponse, METHOD_COMPLETE, METHOD_HISTORY,
+    METHOD_INPUT, METHOD_OUTCOME, METHOD_RELOAD, METHOD_SESSION_FORK, METHOD_SESSION_RESET,
+    METHOD_STATUS, OutcomeParams, REQUEST_CANCELLED, ResponseAction, SessionParams, SessionResult,
+    config_error, internal_error, invalid_params, invalid_request, method_not_found,
+    request_cancelled,
 };
 use provider::{CommandGenerator, Context};
 use serde_json::Value;
 use session::SessionStore;
+use status::{Stats, StatusReport};
 use std::fs;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{UnixListener, UnixStream};
-use tokio::time::{Duration, timeout};
-use tracing::{error, info};
-use tracing_subscriber::EnvFilter;
+use std::os::fd::AsFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, Interest};
+use tokio::net::UnixStream;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant, sleep_until, timeout};
+use tracing::{debug, error, info, warn};
 
 const HANDSHAKE_TIMEOUT_MS: u64 = 200;
 
@@ -37,7 +59,11 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Start the backend daemon
-    Daemon,
+    Daemon {
+        /// Shut down an already running daemon and take over from it
+        #[arg(long)]
+        replace: bool,
+    },
     /// Ask the AI for a command
     Ask {
         /// The query/prompt
@@ -45,9 +71,90 @@ enum Commands {
         /// Current working directory
         #[arg(long, default_value = ".")]
         pwd: String,
+        /// File holding the output of the previous command, to use as context
+        #[arg(long)]
+        output_file: Option<PathBuf>,
+        /// Exit status of the command that produced --output-file
+        #[arg(long, requires = "output_file", allow_hyphen_values = true)]
+        exit_status: Option<i32>,
+        /// Shell the command is generated for
+        #[arg(long, value_enum)]
+        shell: Option<shell::Shell>,
+        /// Version of --shell, e.g. "5.9"
+        #[arg(long, requires = "shell")]
+        shell_version: Option<String>,
+        /// Alias or function defined in the shell, which the generated command may use
+        #[arg(long = "alias", value_name = "NAME")]
+        aliases: Vec<String>,
+    },
+    /// Suggest a command for a prompt that is still being typed (used by the shell plugin)
+    #[command(hide = true)]
+    Complete {
+        prompt: String,
+        #[arg(long, default_value = ".")]
+        pwd: String,
+        #[arg(long, value_enum)]
+        shell: Option<shell::Shell>,
+        #[arg(long, requires = "shell")]
+        shell_version: Option<String>,
+        /// Record this suggestion for the prompt as taken by the user
+        #[arg(long, value_name = "COMMAND")]
+        accept: Option<String>,
+    },
+    /// Show the status of the running daemon
+    Status {
+        /// Print the raw status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Search, show or export past prompts and the commands generated for them
+    History {
+        /// Fuzzy search over prompts and commands
+        query: Option<String>,
+        /// Only this terminal's session (see CHITIN_SESSION_ID)
+        #[arg(long)]
+        session: bool,
+        /// Only entries from this day on (YYYY-MM-DD)
+        #[arg(long, value_parser = history::parse_date)]
+        since: Option<NaiveDate>,
+        /// Only entries up to and including this day (YYYY-MM-DD)
+        #[arg(long, value_parser = history::parse_date)]
+        until: Option<NaiveDate>,
+        /// Only commands generated in this directory or below it
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Only commands that were run, as generated or after editing
+        #[arg(long)]
+        accepted: bool,
+        /// Show only the most recent (or best matching) entries [default: 1000, 0 for all]
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = history::ExportFormat::Text)]
+        format: history::ExportFormat,
+    },
+    /// Report what happened to the last generated command (used by the shell plugin)
+    #[command(hide = true)]
+    Outcome {
+        #[arg(value_enum)]
+        outcome: audit::Outcome,
+        /// The command that ran instead, if it was edited
+        #[arg(long)]
+        command: Option<String>,
+    },
+    /// Manage this terminal's conversation with the daemon
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
     },
     /// Manage the background service
     Service {
+        /// Print what would be executed instead of changing anything
+        #[arg(long, global = true)]
+        dry_run: bool,
+        /// Service manager to use instead of detecting it
+        #[arg(long = "type", value_enum, global = true)]
+        service_type: Option<service::ServiceType>,
         #[command(subcommand)]
         command: ServiceCommands,
     },
@@ -67,14 +174,71 @@ enum ServiceCommands {
     },
     /// Install and start the service automatically
     Install,
-    /// Reload the daemon configuration
+    /// Stop the service and remove its files
+    Uninstall,
+    /// Show whether the service is running and enabled
+    Status,
+    /// Start the service
+    Start,
+    /// Stop the service
+    Stop,
+    /// Show the daemon log
+    Logs {
+        /// Keep printing new log lines
+        #[arg(short, long)]
+        follow: bool,
+        /// Number of lines to show
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+    },
+    /// Reload the daemon configuration and report whether it was valid
     Reload,
 }
 
+#[derive(Subcommand)]
+enum SessionCommand {
+    /// Print the current session id
+    Id,
+    /// Forget the prompts and last command of the current session
+    Reset,
+    /// Copy a session's history into a new session and print its id
+    Fork {
+        /// Session to copy, defaults to the current one
+        #[arg(long)]
+        from: Option<String>,
+        /// Id for the new session, generated when omitted
+        #[arg(long)]
+        to: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 enum ShellCommand {
-    /// Install the Zsh plugin
-    Install,
+    /// Install the shell plugin and source it from the shell's rc file
+    Install {
+        /// Shell to install for; detected from $SHELL by default
+        #[arg(long, value_enum)]
+        shell: Option<shell::Shell>,
+        /// Install as an oh-my-zsh custom plugin instead of editing .zshrc
+        #[arg(long, conflicts_with = "shell")]
+        oh_my_zsh: bool,
+    },
+    /// Remove the shell plugin and its line in the shell's rc file
+    Uninstall {
+        /// Shell to uninstall for; detected from $SHELL by default
+        #[arg(long, value_enum)]
+        shell: Option<shell::Shell>,
+        /// Remove the oh-my-zsh custom plugin
+        #[arg(long, conflicts_with = "shell")]
+        oh_my_zsh: bool,
+    },
+    /// Print the shell plugin, e.g. for eval "$(chitin shell init zsh)"
+    Init {
+        #[arg(value_enum)]
+        shell: shell::Shell,
+    },
+    /// Refresh installed shell plugins after upgrading chitin
+    Update,
 }
 
 use config::Config;
@@ -85,94 +249,299 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Some(Commands::Ask { prompt, pwd }) => {
-            client::run(prompt, pwd).await?;
+        Some(Commands::Ask {
+            prompt,
+            pwd,
+            output_file,
+            exit_status,
+            shell,
+            shell_version,
+            aliases,
+        }) => {
+            let output = client::OutputCapture {
+                file: output_file,
+                exit_status,
+            };
+            let shell = client::ShellInfo {
+                shell,
+                version: shell_version,
+                aliases,
+            };
+            client::run(prompt, pwd, output, shell).await?;
+        }
+        Some(Commands::Complete {
+            prompt,
+            pwd,
+            shell,
+            shell_version,
+            accept,
+        }) => {
+            let shell = client::ShellInfo {
+                shell,
+                version: shell_version,
+                aliases: Vec::new(),
+            };
+            client::complete(prompt, pwd, shell, accept).await?;
+        }
+        Some(Commands::Status { json }) => {
+            status::run(json).await?;
+        }
+        Some(Commands::History {
+            query,
+            session,
+            since,
+            until,
+            dir,
+            accepted,
+            limit,
+            format,
+        }) => {
+            let filter = history::Filter {
+                query,
+                session: session.then(client::session_id),
+                since,
+                until,
+                dir,
+                accepted,
+                limit,
+            };
+            history::run(filter, format).await?;
         }
-        Some(Commands::Service { command }) => match command {
+        Some(Commands::Session { command }) => match command {
+            SessionCommand::Id => println!("{}", client::session_id()),
+            SessionCommand::Reset => client::reset_session().await?,
+            SessionCommand::Fork { from, to } => client::fork_session(from, to).await?,
+        },
+        Some(Commands::Outcome { outcome, command }) => {
+            client::report_outcome(outcome, command).await?;
+        }
+        Some(Commands::Service {
+            dry_run,
+            service_type,
+            command,
+        }) => match command {
             ServiceCommands::Generate { type_ } => {
-                let content = service::generate(type_)?;
-                print!("{}", content);
+                let files = service::generate(type_)?;
+                let multiple = files.len() > 1;
+                for file in files {
+                    if multiple {
+                        println!("# ==> {} <==", file.name);
+                    }
+                    print!("{}", file.content);
+                    if multiple {
+                        println!();
+                    }
+                }
             }
             ServiceCommands::Install => {
-                service::install()?;
+                service::install(&service::Runner::new(dry_run), service_type)?;
+            }
+            ServiceCommands::Uninstall => {
+                service::uninstall(&service::Runner::new(dry_run), service_type)?;
+            }
+            ServiceCommands::Status => {
+                service::status(&service::Runner::new(dry_run), service_type)?;
+            }
+            ServiceCommands::Start => {
+                service::start(&service::Runner::new(dry_run), service_type)?;
+            }
+            ServiceCommands::Stop => {
+                service::stop(&service::Runner::new(dry_run), service_type)?;
+            }
+            ServiceCommands::Logs { follow, lines } => {
+                service::logs(&service::Runner::new(dry_run), service_type, follow, lines)?;
             }
             ServiceCommands::Reload => {
-                service::reload()?;
+                service::reload().await?;
             }
         },
         Some(Commands::Shell { command }) => match command {
-            ShellCommand::Install => {
-                shell::install()?;
+            ShellCommand::Install { shell, oh_my_zsh } => {
+                shell::install(shell, oh_my_zsh)?;
+            }
+            ShellCommand::Uninstall { shell, oh_my_zsh } => {
+                shell::uninstall(shell, oh_my_zsh)?;
+            }
+            ShellCommand::Init { shell } => {
+                shell::init(shell)?;
+            }
+            ShellCommand::Update => {
+                shell::update()?;
             }
         },
-        Some(Commands::Daemon) | None => {
+        Some(Commands::Daemon { replace }) => {
+            let config = Config::load();
+            let _log_guard = logging::init(&config.log)?;
+            run_daemon(config, replace).await?;
+        }
+        None => {
             // Default to daemon mode
-            init_logging();
             let config = Config::load();
-            run_daemon(config).await?;
+            let _log_guard = logging::init(&config.log)?;
+            run_daemon(config, false).await?;
         }
     }
 
     Ok(())
 }
 
-fn init_logging() {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(false)
-        .with_ansi(true)
-        .pretty()
-        .init();
+/// State shared by every connection handled by the daemon
+struct DaemonState {
+    sessions: Mutex<SessionStore>,
+    // Wrap provider in RwLock for hot-swap
+    provider: tokio::sync::RwLock<Box<dyn CommandGenerator>>,
+    config: RwLock<Config>,
+    stats: Stats,
+    audit: AuditLog,
+    completions: complete::Completions,
+    in_flight: AtomicUsize,
+    last_activity: Mutex<Instant>,
+    /// Notified whenever the last in-flight connection finishes
+    drained: Notify,
 }
 
-async fn run_daemon(config: Config) -> Result<()> {
-    init_socket(&config)?;
-    let listener = UnixListener::bind(&config.server.socket_path)?;
-    info!("Chitin: listening on {}", config.server.socket_path);
+impl DaemonState {
+    fn touch(&self) {
+        *self.last_activity.lock().expect("activity lock") = Instant::now();
+    }
 
-    let session_store = Arc::new(Mutex::new(SessionStore::new(10)));
+    /// Whether nothing has happened for at least `idle`
+    fn is_idle(&self, idle: Duration) -> bool {
+        self.in_flight.load(Ordering::SeqCst) == 0
+            && self.last_activity.lock().expect("activity lock").elapsed() >= idle
+    }
 
-    // Wrap provider in RwLock for hot-swap
-    let provider = Arc::new(tokio::sync::RwLock::new(provider::build_provider(&config)?));
+    /// Wait for in-flight connections to finish, up to `deadline`.
+    /// Returns how many were still running when it gave up.
+    async fn drain(&self, deadline: Duration) -> usize {
+        let wait = async {
+            loop {
+                // Registered before checking, so a wakeup in between is not lost
+                let drained = self.drained.notified();
+                if self.in_flight.load(Ordering::SeqCst) == 0 {
+                    return;
+                }
+                drained.await;
+            }
+        };
+        let _ = timeout(deadline, wait).await;
+        self.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// Marks a connection as in flight for as long as it is alive
+struct InFlight(Arc<DaemonState>);
+
+impl InFlight {
+    fn new(state: Arc<DaemonState>) -> Self {
+        state.in_flight.fetch_add(1, Ordering::SeqCst);
+        state.touch();
+        Self(state)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.drained.notify_waiters();
+        }
+        self.0.touch();
+    }
+}
+
+async fn run_daemon(config: Config, replace: bool) -> Result<()> {
+    let socket_path = PathBuf::from(&config.server.socket_path);
+    let inherited = socket::inherited()?;
+    let activated = inherited.is_some();
+    let _instance =
+        instance::acquire((!activated).then_some(socket_path.as_path()), replace).await?;
+    let listener = match inherited {
+        Some(listener) => {
+            info!("Chitin: using socket passed by the service manager");
+            listener
+        }
+        None => {
+            socket::prepare(&socket_path)?;
+            let listener = socket::bind(&socket_path)?;
+            info!("Chitin: listening on {}", config.server.socket_path);
+            listener
+        }
+    };
+
+    let state = Arc::new(DaemonState {
+        sessions: Mutex::new(SessionStore::new(&config.session)),
+        provider: tokio::sync::RwLock::new(provider::build_provider(&config)?),
+        config: RwLock::new(config),
+        stats: Stats::new(),
+        audit: AuditLog::default(),
+        completions: complete::Completions::default(),
+        in_flight: AtomicUsize::new(0),
+        last_activity: Mutex::new(Instant::now()),
+        drained: Notify::new(),
+    });
+
+    // Started either way, since server.watch_config can be turned on by a reload
+    if let Err(err) = watch::spawn(Arc::clone(&state)) {
+        error!("Chitin: failed to watch config file: {}", err);
+    }
 
-    // Listen for SIGHUP
-    let mut sighup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    // Listen for SIGHUP, and SIGTERM/SIGINT for shutdown
+    let mut sighup = signal(SignalKind::hangup())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
 
     loop {
+        // Only a socket activated daemon can exit when idle, since the service
+        // manager starts it again on the next connection
+        let idle_timeout = Duration::from_secs(
+            state
+                .config
+                .read()
+                .expect("config lock")
+                .server
+                .idle_timeout_secs,
+        );
+        let idle_deadline = (activated && !idle_timeout.is_zero())
+            .then(|| *state.last_activity.lock().expect("activity lock") + idle_timeout);
+
         tokio::select! {
+            _ = sleep_until(idle_deadline.unwrap_or_else(Instant::now)), if idle_deadline.is_some() => {
+                if state.is_idle(idle_timeout) {
+                    info!("Chitin: idle for {}s, exiting", idle_timeout.as_secs());
+                    break;
+                }
+                if state.in_flight.load(Ordering::SeqCst) > 0 {
+                    // Busy; check again a full period from now
+                    state.touch();
+                }
+            }
+            _ = sigterm.recv() => {
+                info!("Chitin: received SIGTERM, shutting down...");
+                break;
+            }
+            _ = sigint.recv() => {
+                info!("Chitin: received SIGINT, shutting down...");
+                break;
+            }
             _ = sighup.recv() => {
                 info!("Chitin: received SIGHUP, reloading config...");
-                match Config::load_reload() {
-                    Ok(new_config) => {
-                        match provider::build_provider(&new_config) {
-                            Ok(new_provider) => {
-                                let mut w = provider.write().await;
-                                *w = new_provider;
-                                info!("Chitin: config reloaded successfully");
-                            }
-                            Err(e) => error!("Chitin: failed to build provider from new config: {}", e),
-                        }
-                    }
-                    Err(e) => error!("Chitin: failed to reload config: {}", e),
-                }
+                // Errors are logged by reload itself; the old provider stays active
+                let _ = reload::reload(&state).await;
             }
             accept_result = listener.accept() => {
                  match accept_result {
                     Ok((stream, _)) => {
-                        let store = Arc::clone(&session_store);
-                        let provider_lock = Arc::clone(&provider);
+                        let uid = socket::peer_uid(&stream);
+                        if uid != Some(socket::current_uid()) {
+                            warn!("Chitin: rejected connection from uid {:?}", uid);
+                            continue;
+                        }
+                        let in_flight = InFlight::new(Arc::clone(&state));
                         tokio::spawn(async move {
-                            // Acquire read lock for the duration of the request generation?
-                            // Or just clone the provider (if it was Arc internal)?
-                            // Since `CommandGenerator` is a trait object, we need to access it.
-                            // To avoid holding the lock for too long (blocking reload),
-                            // we probably want to get a reference to the current provider.
-                            // But `CommandGenerator` is dynamic.
-                            // Simplest approach: hold read lock during generation.
-                            // `handle_connection` will need to take the RwLock.
-
-                            if let Err(err) = handle_connection(stream, store, provider_lock).await {
+                            // The provider read lock is held for the duration of a generation,
+                            // so a reload waits for in-flight requests to finish.
+                            let state = Arc::clone(&in_flight.0);
+                            if let Err(err) = handle_connection(stream, state).await {
                                 error!("Chitin error: {err}");
                             }
                         });
@@ -184,21 +553,36 @@ async fn run_daemon(config: Config) -> Result<()> {
             }
         }
     }
-}
 
-fn init_socket(config: &Config) -> Result<()> {
-    let path = Path::new(&config.server.socket_path);
-    if path.exists() {
-        fs::remove_file(path)?;
+    // Stop accepting new connections. An inherited socket belongs to the
+    // service manager, which keeps listening and starts us again on demand.
+    drop(listener);
+    if !activated {
+        let _ = fs::remove_file(&socket_path);
+    }
+
+    let deadline = Duration::from_secs(
+        state
+            .config
+            .read()
+            .expect("config lock")
+            .server
+            .shutdown_timeout_secs,
+    );
+    let remaining = state.drain(deadline).await;
+    if remaining > 0 {
+        warn!(
+            "Chitin: {} request(s) still in flight after {}s, abandoning them",
+            remaining,
+            deadline.as_secs()
+        );
     }
+
+    info!("Chitin: stopped");
     Ok(())
 }
 
-async fn handle_connection(
-    mut stream: UnixStream,
-    sessions: Arc<Mutex<SessionStore>>,
-    provider_lock: Arc<tokio::sync::RwLock<Box<dyn CommandGenerator>>>,
-) -> Result<()> {
+async fn handle_connection(mut stream: UnixStream, state: Arc<DaemonState>) -> Result<()> {
     let mut buffer = Vec::new();
     let read_result = timeout(
         Duration::from_millis(HANDSHAKE_TIMEOUT_MS),
@@ -234,7 +618,16 @@ async fn handle_connection(
         }
     };
 
-    let response = handle_request(request, sessions, provider_lock).await;
+    // Shells cancel a request by killing the client; stop generating then,
+    // rather than keep a command in the history that nobody saw
+    let cancellable = matches!(request.method.as_str(), METHOD_INPUT | METHOD_COMPLETE);
+    let response = tokio::select! {
+        response = handle_request(request, &state) => response,
+        () = client_gone(&stream), if cancellable => {
+            debug!("Chitin: client went away, request cancelled");
+            return Ok(());
+        }
+    };
     if let Err(err) = send_response(&mut stream, response).await {
         if is_broken_pipe(&err) {
             return Ok(());
@@ -244,34 +637,198 @@ async fn handle_connection(
     Ok(())
 }
 
-async fn handle_request(
-    request: JsonRpcRequest,
-    sessions: Arc<Mutex<SessionStore>>,
-    provider_lock: Arc<tokio::sync::RwLock<Box<dyn CommandGenerator>>>,
-) -> JsonRpcResponse {
+/// Resolves once the client has closed its end of the connection entirely.
+///
+/// Having sent its request, a client only shuts down its writing half, so
+/// the socket stays writable until the client itself is gone. That is watched
+/// on a duplicate of the socket, so the response can still be written to the
+/// original.
+async fn client_gone(stream: &UnixStream) {
+    let watched = stream
+        .as_fd()
+        .try_clone_to_owned()
+        .and_then(|fd| AsyncFd::with_interest(fd, Interest::WRITABLE));
+    let Ok(watched) = watched else {
+        return std::future::pending().await;
+    };
+    loop {
+        match watched.writable().await {
+            Ok(guard) if guard.ready().is_write_closed() => return,
+            Ok(mut guard) => guard.clear_ready(),
+            Err(_) => return,
+        }
+    }
+}
+
+async fn handle_request(request: JsonRpcRequest, state: &DaemonState) -> JsonRpcResponse {
+    state.stats.record_request();
+    let response = dispatch_request(request, state).await;
+    // A suggestion replaced by the next keystroke is not a failure
+    if response
+        .error
+        .as_ref()
+        .is_some_and(|error| error.code != REQUEST_CANCELLED)
+    {
+        state.stats.record_error();
+    }
+    response
+}
+
+async fn dispatch_request(request: JsonRpcRequest, state: &DaemonState) -> JsonRpcResponse {
     if request.jsonrpc != "2.0" {
         return invalid_request(request.id, "jsonrpc must be 2.0");
     }
 
-    if request.method != "chitin.input" {
-        return method_not_found(request.id, "unknown method");
+    match request.method.as_str() {
+        METHOD_INPUT => handle_input(request, state).await,
+        METHOD_COMPLETE => handle_complete(request, state).await,
+        METHOD_STATUS => handle_status(request, state).await,
+        METHOD_OUTCOME => handle_outcome(request, state).await,
+        METHOD_HISTORY => handle_history(request, state).await,
+        METHOD_SESSION_RESET | METHOD_SESSION_FORK => handle_session(request, state),
+        METHOD_RELOAD => match reload::reload(state).await {
+            Ok(report) => JsonRpcResponse::success(request.id, report),
+            Err(message) => config_error(request.id, message),
+        },
+        _ => method_not_found(request.id, "unknown method"),
+    }
+}
+
+async fn handle_status(request: JsonRpcRequest, state: &DaemonState) -> JsonRpcResponse {
+    let sessions = state.sessions.lock().expect("session lock").len();
+    let generator = state.provider.read().await;
+    let config = state.config.read().expect("config lock");
+    let report = StatusReport::collect(&config, generator.model(), sessions, &state.stats);
+    JsonRpcResponse::success(request.id, report)
+}
+
+fn handle_session(request: JsonRpcRequest, state: &DaemonState) -> JsonRpcResponse {
+    let params: SessionParams = match serde_json::from_value(request.params) {
+        Ok(params) => params,
+        Err(err) => return invalid_params(request.id, format!("invalid params: {err}")),
+    };
+
+    let scope = state.config.read().expect("config lock").session.scope;
+    if request.method == METHOD_SESSION_FORK && scope != ContextScope::Session {
+        return invalid_params(
+            request.id,
+            "sessions can only be forked when session.scope is \"session\"",
+        );
+    }
+
+    let mut store = state.sessions.lock().expect("session lock");
+    let result = if request.method == METHOD_SESSION_RESET {
+        let pwd = params.pwd.as_deref().unwrap_or("/");
+        store.reset(&session::scope_key(scope, &params.session_id, pwd));
+        info!("Chitin: session reset");
+        SessionResult {
+            session_id: params.session_id,
+            history: 0,
+        }
+    } else {
+        let new_session_id = params.new_session_id.unwrap_or_else(|| {
+            let micros = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros();
+            format!("{}+{:x}", params.session_id, micros)
+        });
+        let history = store.fork(&params.session_id, &new_session_id);
+        info!("Chitin: session forked");
+        SessionResult {
+            session_id: new_session_id,
+            history,
+        }
+    };
+    store.evict();
+    drop(store);
+    state
+        .audit
+        .trim(&state.config.read().expect("config lock").session);
+    JsonRpcResponse::success(request.id, result)
+}
+
+async fn handle_history(request: JsonRpcRequest, state: &DaemonState) -> JsonRpcResponse {
+    let filter = match request.params {
+        Value::Null => Ok(history::Filter::default()),
+        params => serde_json::from_value(params),
+    };
+    let filter: history::Filter = match filter {
+        Ok(filter) => filter,
+        Err(err) => return invalid_params(request.id, format!("invalid params: {err}")),
+    };
+
+    // The log can be large, so keep reading and parsing it off the workers
+    // that serve other requests
+    let path = audit::path(&state.config.read().expect("config lock").audit);
+    let records = tokio::task::spawn_blocking(move || {
+        audit::read(&path).map(|records| history::select(records, &filter))
+    })
+    .await;
+    match records {
+        Ok(Ok(records)) => JsonRpcResponse::success(request.id, records),
+        Ok(Err(err)) => internal_error(request.id, format!("{err:#}")),
+        Err(err) => internal_error(request.id, format!("history task failed: {err}")),
+    }
+}
+
+async fn handle_outcome(request: JsonRpcRequest, state: &DaemonState) -> JsonRpcResponse {
+    let params: OutcomeParams = match serde_json::from_value(request.params) {
+        Ok(params) => params,
+        Err(err) => return invalid_params(request.id, format!("invalid params: {err}")),
+    };
+
+    let config = state.config.read().expect("config lock").audit.clone();
+    match state
+        .audit
+        .record_outcome(&config, &params.session_id, params.outcome, params.command)
+        .await
+    {
+        Ok(id) => JsonRpcResponse::success(request.id, serde_json::json!({ "id": id })),
+        Err(err) => {
+            error!("Chitin: failed to write audit log: {err:#}");
+            internal_error(request.id, err.to_string())
+        }
     }
+}
+
+async fn handle_input(request: JsonRpcRequest, state: &DaemonState) -> JsonRpcResponse {
+    let params: InputParams = match serde_json::from_value(request.params) {
+        Ok(params) => params,
+        Err(err) => return invalid_params(request.id, format!("invalid params: {err}")),
+    };
 
-    if request.params.prompt.trim().is_empty() {
+    if params.prompt.trim().is_empty() {
         return invalid_params(request.id, "prompt is required");
     }
 
-    let session_id = request.params.session_id.clone();
-    let prompt = request.params.prompt.clone();
-    let pwd = request.params.pwd.clone();
+    let session_id = params.session_id.clone();
+    let prompt = params.prompt.clone();
+    let pwd = params.pwd.clone();
 
+    let (scope, output) = {
+        let config = state.config.read().expect("config lock");
+        let output = params
+            .output
+            .clone()
+            .and_then(|output| capture::prepare(output, &config.capture));
+        (config.session.scope, output)
+    };
+    let scope_key = session::scope_key(scope, &session_id, &pwd);
     let snapshot = {
-        let mut store = sessions.lock().expect("session lock");
-        store.record_input(&session_id, &prompt);
-        store.snapshot(&session_id)
+        let mut store = state.sessions.lock().expect("session lock");
+        store.record_input(&scope_key, &prompt);
+        store.evict();
+        store.snapshot(&scope_key)
     };
 
-    info!("Chitin: generating command...");
+    // Prompts and commands can contain secrets, so they are only logged on request
+    let log_prompts = state.config.read().expect("config lock").log.log_prompts;
+    if log_prompts {
+        info!(session = %session_id, prompt = %prompt, "Chitin: generating command...");
+    } else {
+        info!("Chitin: generating command...");
+    }
 
     let context = Context {
         prompt,
@@ -279,25 +836,87 @@ async fn handle_request(
         session_id,
         history: snapshot.history,
         last_command: snapshot.last_command,
+        output,
+        shell: params.shell.clone(),
+        shell_version: params.shell_version.clone(),
+        feedback: None,
+    };
+    let checks = validate::Environment {
+        shell: params.shell.as_deref(),
+        pwd: &params.pwd,
+        path: params.path.as_deref(),
+        aliases: &params.aliases,
     };
 
-    let generation_result = {
-        let generator = provider_lock.read().await;
-        generator.generate(context).await
+    let started = Instant::now();
+    let (generation_result, warning, model) = {
+        let generator = state.provider.read().await;
+        let mut result = generator.generate(context.clone()).await;
+        let rejected = match &result {
+            Ok(command) => validate::check(&checks, command)
+                .await
+                .err()
+                .map(|problem| (command.clone(), problem)),
+            Err(_) => None,
+        };
+        // Models occasionally return unbalanced quotes or made up binaries;
+        // telling them what was wrong usually fixes that
+        let mut warning = None;
+        if let Some((command, problem)) = rejected {
+            if log_prompts {
+                warn!("Chitin: rejected generated command, retrying: {problem}");
+            } else {
+                warn!(
+                    "Chitin: rejected generated command, retrying: {}",
+                    problem.kind()
+                );
+            }
+            let mut retry = context;
+            retry.feedback = Some(format!("`{command}`: {problem}"));
+            result = generator.generate(retry).await;
+            if let Ok(command) = &result {
+                warning = validate::check(&checks, command)
+                    .await
+                    .err()
+                    .map(|problem| format!("the generated command may not work: {problem}"));
+            }
+        }
+        (result, warning, generator.model().to_string())
     };
+    state
+        .stats
+        .record_generation(started.elapsed(), generation_result.is_ok());
 
     match generation_result {
         Ok(command) => {
-            {
-                let mut store = sessions.lock().expect("session lock");
-                store.record_output(&request.params.session_id, &command);
+            let provider = state
+                .config
+                .read()
+                .expect("config lock")
+                .provider
+                .type_
+                .clone();
+            let mut entry = Generated::new(
+                params.session_id,
+                params.pwd,
+                params.prompt,
+                provider,
+                model,
+                command.clone(),
+            );
+            entry.safety = warning.clone();
+            record_command(state, &scope_key, entry).await;
+            if log_prompts {
+                info!(command = %command, "Chitin: done");
+            } else {
+                info!("Chitin: done");
             }
-            info!("Chitin: done");
             JsonRpcResponse::success(
                 request.id,
                 ResponseAction {
                     action_type: "refill".to_string(),
                     command,
+                    warning,
                 },
             )
         }
@@ -308,6 +927,138 @@ async fn handle_request(
     }
 }
 
+/// Keep a command handed to the user as the session's last one and in the audit log
+async fn record_command(state: &DaemonState, scope_key: &str, entry: Generated) {
+    state
+        .sessions
+        .lock()
+        .expect("session lock")
+        .record_output(scope_key, &entry.command);
+    let (audit, sessions) = {
+        let config = state.config.read().expect("config lock");
+        (config.audit.clone(), config.session.clone())
+    };
+    if let Err(err) = state.audit.record_generation(&audit, entry).await {
+        error!("Chitin: failed to write audit log: {err:#}");
+    }
+    state.audit.trim(&sessions);
+}
+
+/// Suggest a command for a prompt as it is typed.
+///
+/// Unlike `chitin.input` this leaves the session alone, skips the checks on
+/// the command and gives up after `complete.timeout_ms`. Only a suggestion the
+/// user takes (sent back as `accepted`) is recorded.
+async fn handle_complete(request: JsonRpcRequest, state: &DaemonState) -> JsonRpcResponse {
+    let params: CompleteParams = match serde_json::from_value(request.params) {
+        Ok(params) => params,
+        Err(err) => return invalid_params(request.id, format!("invalid params: {err}")),
+    };
+
+    let prompt = params.prompt.trim();
+    if prompt.is_empty() {
+        return invalid_params(request.id, "prompt is required");
+    }
+
+    let (config, scope) = {
+        let config = state.config.read().expect("config lock");
+        (config.complete.clone(), config.session.scope)
+    };
+    if !config.enabled {
+        return invalid_request(request.id, "completions are disabled");
+    }
+    let scope_key = session::scope_key(scope, &params.session_id, &params.pwd);
+
+    if let Some(command) = params.accepted {
+        {
+            let mut store = state.sessions.lock().expect("session lock");
+            store.record_input(&scope_key, prompt);
+            store.evict();
+        }
+        let model = state.provider.read().await.model().to_string();
+        let provider = state
+            .config
+            .read()
+            .expect("config lock")
+            .provider
+            .type_
+            .clone();
+        let entry = Generated::new(
+            params.session_id,
+            params.pwd,
+            prompt.to_string(),
+            provider,
+            model,
+            command.clone(),
+        );
+        record_command(state, &scope_key, entry).await;
+        info!("Chitin: suggestion accepted");
+        return JsonRpcResponse::success(
+            request.id,
+            ResponseAction {
+                action_type: "refill".to_string(),
+                command,
+                warning: None,
+            },
+        );
+    }
+
+    let key = complete::key(params.shell.as_deref(), &params.pwd, prompt);
+    let command = match state.completions.cached(&key) {
+        Some(command) => command,
+        None => {
+            let mut running = state.completions.start(&params.session_id);
+            let snapshot = state
+                .sessions
+                .lock()
+                .expect("session lock")
+                .snapshot(&scope_key);
+            let context = Context {
+                prompt: prompt.to_string(),
+                pwd: params.pwd.clone(),
+                session_id: params.session_id.clone(),
+                history: snapshot.history,
+                last_command: snapshot.last_command,
+                output: None,
+                shell: params.shell.clone(),
+                shell_version: params.shell_version.clone(),
+                feedback: None,
+            };
+
+            let generator = state.provider.read().await;
+            let limit = Duration::from_millis(config.timeout_ms);
+            let result = tokio::select! {
+                result = timeout(limit, generator.generate(context)) => result,
+                _ = &mut running.cancelled => {
+                    return request_cancelled(request.id, "replaced by a newer request");
+                }
+            };
+            match result {
+                Ok(Ok(command)) => {
+                    state
+                        .completions
+                        .store(key, command.clone(), config.cache_size);
+                    command
+                }
+                Ok(Err(err)) => {
+                    debug!("Chitin: suggestion failed - {err}");
+                    return internal_error(request.id, err.to_string());
+                }
+                Err(_) => return internal_error(request.id, "suggestion timed out"),
+            }
+        }
+    };
+
+    JsonRpcResponse::success(
+        request.id,
+        ResponseAction {
+            action_type: "suggest".to_string(),
+            command,
+            warning: None,
+        },
+    )
+}
+
 async fn send_response(stream: &mut UnixStream, response: JsonRpcResponse) -> Result<()> {
     let payload = serde_json::to_vec(&response)?;
     stream.write_all(&payload).await?;
@@ -1,12 +1,25 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+pub const METHOD_INPUT: &str = "chitin.input";
+pub const METHOD_STATUS: &str = "chitin.status";
+pub const METHOD_RELOAD: &str = "chitin.reload";
+pub const METHOD_OUTCOME: &str = "chitin.outcome";
+pub const METHOD_HISTORY: &str = "chitin.history";
+pub const METHOD_SESSION_RESET: &str = "chitin.session.reset";
+pub const METHOD_SESSION_FORK: &str = "chitin.session.fork";
+pub const METHOD_COMPLETE: &str = "chitin.complete";
+
+/// Error code of a request given up on because a newer one replaced it
+pub const REQUEST_CANCELLED: i32 = -32800;
+
 #[derive(Debug, Deserialize)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
     pub id: Value,
     pub method: String,
-    pub params: InputParams,
+    #[serde(default)]
+    pub params: Value,
 }
 
 #[derive(Debug, Deserialize)]
@@ -14,6 +27,71 @@ pub struct InputParams {
     pub prompt: String,
     pub pwd: String,
     pub session_id: String,
+    /// Output of the previous command, when the shell captured it
+    #[serde(default)]
+    pub output: Option<CommandOutput>,
+    /// Shell the command is generated for, e.g. "zsh", "fish", "nu" or "pwsh"
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// Version of that shell, e.g. "5.9"
+    #[serde(default)]
+    pub shell_version: Option<String>,
+    /// `PATH` of the shell, to check that the generated command exists
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Aliases and functions defined in the shell, which count as existing commands
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+/// A prompt that is still being typed, for a suggestion shown as it is
+#[derive(Debug, Deserialize)]
+pub struct CompleteParams {
+    pub prompt: String,
+    pub pwd: String,
+    pub session_id: String,
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub shell_version: Option<String>,
+    /// Suggestion the user took for this prompt; it is recorded like a
+    /// generated command instead of asking the model again
+    #[serde(default)]
+    pub accepted: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandOutput {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_status: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OutcomeParams {
+    pub session_id: String,
+    pub outcome: crate::audit::Outcome,
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionParams {
+    pub session_id: String,
+    /// Directory of the caller, which picks the history to reset when it is
+    /// scoped by project or directory
+    #[serde(default)]
+    pub pwd: Option<String>,
+    /// Id for the copy made by `chitin.session.fork`, generated when missing
+    #[serde(default)]
+    pub new_session_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResult {
+    pub session_id: String,
+    /// Prompts in the session after the operation
+    pub history: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,7 +99,7 @@ pub struct JsonRpcResponse {
     pub jsonrpc: String,
     pub id: Value,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub result: Option<ResponseAction>,
+    pub result: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<JsonRpcError>,
 }
@@ -31,6 +109,10 @@ pub struct ResponseAction {
     #[serde(rename = "type")]
     pub action_type: String,
     pub command: String,
+    /// Something the user should know before running the command, such as an
+    /// unknown binary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,11 +124,11 @@ pub struct JsonRpcError {
 }
 
 impl JsonRpcResponse {
-    pub fn success(id: Value, action: ResponseAction) -> Self {
+    pub fn success(id: Value, result: impl Serialize) -> Self {
         Self {
             jsonrpc: "2.0".to_string(),
             id,
-            result: Some(action),
+            result: Some(serde_json::to_value(result).expect("serializable result")),
             error: None,
         }
     }
@@ -80,3 +162,11 @@ pub fn invalid_params(id: Value, message: impl Into<String>) -> JsonRpcResponse
 pub fn internal_error(id: Value, message: impl Into<String>) -> JsonRpcResponse {
     JsonRpcResponse::error(id, -32603, message, None)
 }
+
+pub fn request_cancelled(id: Value, message: impl Into<String>) -> JsonRpcResponse {
+    JsonRpcResponse::error(id, REQUEST_CANCELLED, message, None)
+}
+
+pub fn config_error(id: Value, message: impl Into<String>) -> JsonRpcResponse {
+    JsonRpcResponse::error(id, -32000, message, None)
+}
@@ -1,6 +1,8 @@
+use crate::protocol::CommandOutput;
 use anyhow::{Result, anyhow};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use tracing::debug;
 
 #[derive(Debug, Clone)]
 pub struct Context {
@@ -9,11 +11,19 @@ pub struct Context {
     pub session_id: String,
     pub history: Vec<String>,
     pub last_command: Option<String>,
+    /// Redacted and truncated output of the previous command
+    pub output: Option<CommandOutput>,
+    /// Shell the command will run in, as sent by the plugin
+    pub shell: Option<String>,
+    pub shell_version: Option<String>,
+    /// The previous attempt and why it was rejected, when generating again
+    pub feedback: Option<String>,
 }
 
 #[async_trait::async_trait]
 pub trait CommandGenerator: Send + Sync {
     async fn generate(&self, context: Context) -> Result<String>;
+    fn model(&self) -> &str;
 }
 
 use crate::config::Config;
@@ -37,6 +47,10 @@ impl CommandGenerator for NoopProvider {
         }
         Ok(format!("echo \"Chitin: {prompt}\""))
     }
+
+    fn model(&self) -> &str {
+        "noop"
+    }
 }
 
 pub struct OpenAiCompatibleProvider {
@@ -87,18 +101,84 @@ impl OpenAiCompatibleProvider {
             details.push(format!("recent_prompts: {}", context.history.join(" | ")));
         }
 
+        let mut system = "You are a shell command generator. Return exactly one executable command, no commentary, no markdown.".to_string();
+        if let Some(shell) = &context.shell {
+            let version = context
+                .shell_version
+                .as_deref()
+                .map(|version| format!(" {version}"))
+                .unwrap_or_default();
+            system.push_str(&format!(
+                " The command runs in {}{version}; use its syntax and builtins.",
+                shell_name(shell)
+            ));
+            if let Some(notes) = dialect_notes(shell) {
+                system.push(' ');
+                system.push_str(notes);
+            }
+        }
         let system = Message {
             role: "system".to_string(),
-            content: "You are a shell command generator. Return exactly one executable command, no commentary, no markdown.".to_string(),
+            content: system,
         };
+        let mut content = format!("Task: {}\nContext: {}", context.prompt, details.join("; "));
+        if let Some(output) = &context.output {
+            let status = output
+                .exit_status
+                .map(|status| format!(" (exit status {status})"))
+                .unwrap_or_default();
+            content.push_str(&format!(
+                "\nOutput of the previous command{status}:\n{}",
+                output.text
+            ));
+        }
+        if let Some(feedback) = &context.feedback {
+            content.push_str(&format!(
+                "\nYour previous answer was rejected: {feedback}\nReturn a corrected command."
+            ));
+        }
         let user = Message {
             role: "user".to_string(),
-            content: format!("Task: {}\nContext: {}", context.prompt, details.join("; ")),
+            content,
         };
         vec![system, user]
     }
 }
 
+/// Readable name of a shell as sent by the plugins, for the prompt
+fn shell_name(shell: &str) -> &str {
+    match shell {
+        "nu" => "Nushell",
+        "pwsh" | "powershell" => "PowerShell",
+        "zsh" => "Zsh",
+        "bash" => "Bash",
+        "fish" => "fish",
+        other => other,
+    }
+}
+
+/// Differences between shells that models tend to get wrong
+fn dialect_notes(shell: &str) -> Option<&'static str> {
+    match shell {
+        "zsh" => Some(
+            "Recursive globs (**/*) and glob qualifiers such as *(.om[1]) work; arrays are 1-indexed; prefer print -r -- over echo for arbitrary text.",
+        ),
+        "bash" => Some(
+            "There are no glob qualifiers; **/* needs shopt -s globstar; arrays are 0-indexed; use printf rather than print.",
+        ),
+        "fish" => Some(
+            "fish is not POSIX: use set NAME value instead of NAME=value, (cmd) for command substitution, and; or; not instead of && || !, and no heredocs.",
+        ),
+        "nu" => Some(
+            "Nushell pipelines carry structured data: filter with where, select and sort-by (e.g. ls | where size > 1mb) instead of parsing text with find, grep or awk.",
+        ),
+        "pwsh" | "powershell" => Some(
+            "Use PowerShell cmdlets and object pipelines (Get-ChildItem, Where-Object, Select-Object) rather than Unix text tools.",
+        ),
+        _ => None,
+    }
+}
+
 #[async_trait::async_trait]
 impl CommandGenerator for OpenAiCompatibleProvider {
     async fn generate(&self, context: Context) -> Result<String> {
@@ -106,6 +186,7 @@ impl CommandGenerator for OpenAiCompatibleProvider {
             "{}/v1/chat/completions",
             self.base_url.trim_end_matches('/')
         );
+        debug!(session = %context.session_id, model = %self.model, "requesting completion");
         let request = ChatRequest {
             model: self.model.clone(),
             messages: self.build_prompt(&context),
@@ -124,7 +205,7 @@ impl CommandGenerator for OpenAiCompatibleProvider {
         let payload: ChatResponse = response.json().await?;
         let content = payload
             .choices
-            .get(0)
+            .first()
             .and_then(|choice| choice.message.content.clone())
             .ok_or_else(|| anyhow!("model response missing content"))?;
 
@@ -134,6 +215,10 @@ impl CommandGenerator for OpenAiCompatibleProvider {
         }
         Ok(command.to_string())
     }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -0,0 +1,76 @@
+use crate::DaemonState;
+use crate::config::Config;
+use crate::provider;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+/// Result of a successful reload, returned by `chitin.reload`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReloadReport {
+    pub config_path: Option<String>,
+    pub provider: String,
+    pub model: String,
+}
+
+/// Re-read the config and swap in a new provider.
+///
+/// The running provider is left untouched if the new config fails to load or
+/// the provider cannot be built from it.
+pub async fn reload(state: &DaemonState) -> Result<ReloadReport, String> {
+    let new_config = Config::load_reload().map_err(|e| {
+        error!("Chitin: failed to reload config: {}", e);
+        format!("invalid config: {e}")
+    })?;
+
+    let new_provider = provider::build_provider(&new_config).map_err(|e| {
+        error!("Chitin: failed to build provider from new config: {}", e);
+        format!("failed to build provider: {e}")
+    })?;
+
+    let report = ReloadReport {
+        config_path: new_config
+            .source
+            .as_ref()
+            .map(|p| p.to_string_lossy().into_owned()),
+        provider: new_config.provider.type_.clone(),
+        model: new_provider.model().to_string(),
+    };
+
+    let mut w = state.provider.write().await;
+    *w = new_provider;
+    let old_config = std::mem::replace(
+        &mut *state.config.write().expect("config lock"),
+        new_config.clone(),
+    );
+    drop(w);
+    // Suggestions came from the old provider
+    state.completions.clear();
+
+    {
+        let mut sessions = state.sessions.lock().expect("session lock");
+        sessions.configure(&new_config.session);
+        sessions.evict();
+    }
+    state.audit.trim(&new_config.session);
+
+    let changes = old_config.diff(&new_config);
+    if changes.is_empty() {
+        info!("Chitin: config reloaded successfully (no changes)");
+    } else {
+        info!("Chitin: config reloaded successfully");
+        for change in &changes {
+            info!("Chitin:   {}", change);
+        }
+    }
+    if old_config.server.socket_path != new_config.server.socket_path {
+        warn!("Chitin: socket_path changes only take effect after a restart");
+    }
+    // log.log_prompts is read per request; the rest configures the subscriber at startup
+    if changes
+        .iter()
+        .any(|c| c.starts_with("log.") && !c.starts_with("log.log_prompts"))
+    {
+        warn!("Chitin: log settings other than log_prompts only take effect after a restart");
+    }
+    Ok(report)
+}
@@ -1,7 +1,18 @@
+use crate::client;
+use crate::config::{Config, LogDestination};
+use crate::instance;
+use crate::logging;
+use crate::protocol::METHOD_RELOAD;
+use crate::reload::ReloadReport;
+use crate::socket;
 use anyhow::{Context, Result};
 use clap::ValueEnum;
 use directories::BaseDirs;
+use std::cell::RefCell;
 use std::env;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
 #[derive(ValueEnum, Clone, Debug)]
 pub enum ServiceType {
@@ -9,22 +20,78 @@ pub enum ServiceType {
     Launchd,
     /// Linux systemd user service
     Systemd,
-    /// Linux OpenRC init script
+    /// Linux OpenRC service (a user service where supported)
     Openrc,
+    /// runit service directory, e.g. on Void Linux
+    Runit,
+    /// s6 service directory
+    S6,
 }
 
-pub fn reload() -> Result<()> {
-    // Send SIGHUP to the daemon
-    // We use pkill to find the process with argument "daemon"
-    let status = std::process::Command::new("pkill")
-        .arg("-HUP")
+pub async fn reload() -> Result<()> {
+    // Only the socket path is needed here; the daemon reports a bad config itself
+    let socket_path = Config::socket_path();
+    let response = match client::call_socket(socket_path, METHOD_RELOAD, serde_json::json!({}))
+        .await
+    {
+        Ok(response) => response,
+        Err(err) => {
+            // Without the daemon's answer, a broken config has to be reported here
+            if let Err(config_err) = Config::load_reload() {
+                anyhow::bail!(
+                    "Could not reach daemon over its socket ({err}), and the config does not \
+                     load: {config_err}"
+                );
+            }
+            eprintln!("Could not reach daemon over its socket ({err}), falling back to SIGHUP.");
+            return reload_with_signal();
+        }
+    };
+
+    if let Some(error) = response.error {
+        anyhow::bail!("Reload failed: {}", error.message);
+    }
+
+    let report: ReloadReport = serde_json::from_value(
+        response
+            .result
+            .ok_or_else(|| anyhow::anyhow!("daemon returned an empty reload result"))?,
+    )?;
+    println!("Configuration reloaded.");
+    println!(
+        "  config:   {}",
+        report
+            .config_path
+            .as_deref()
+            .unwrap_or("(defaults / environment)")
+    );
+    println!("  provider: {} ({})", report.provider, report.model);
+    Ok(())
+}
+
+fn reload_with_signal() -> Result<()> {
+    if let Some(pid) = instance::running_pid() {
+        instance::signal(pid, libc::SIGHUP)?;
+        println!(
+            "Reload signal sent to chitin daemon (pid {pid}). Check the daemon log for the result."
+        );
+        return Ok(());
+    }
+
+    // No PID file, so look for the process, restricted to our own when we know the user
+    let mut command = std::process::Command::new("pkill");
+    command.arg("-HUP");
+    if let Ok(user) = env::var("USER") {
+        command.arg("-u").arg(user);
+    }
+    let status = command
         .arg("-f")
         .arg("chitin daemon")
         .status()
         .context("Failed to execute pkill")?;
 
     if status.success() {
-        println!("Reload signal sent to chitin daemon.");
+        println!("Reload signal sent to chitin daemon. Check the daemon log for the result.");
         Ok(())
     } else {
         Err(anyhow::anyhow!(
@@ -33,30 +100,460 @@ pub fn reload() -> Result<()> {
     }
 }
 
-pub fn install() -> Result<()> {
-    let os = std::env::consts::OS;
-    let service_type = detect_service_type(os);
+const LAUNCHD_LABEL: &str = "com.user.chitin";
+const SYSTEMD_UNITS: [&str; 2] = ["chitin.socket", "chitin.service"];
+/// OpenRC gained user services (`rc-service --user`) in this version
+const OPENRC_USER_SERVICES_SINCE: (u32, u32) = (0, 60);
 
-    match service_type {
-        Some(ServiceType::Launchd) => install_launchd()?,
-        Some(ServiceType::Systemd) => install_systemd()?,
-        Some(ServiceType::Openrc) => {
-            println!("OpenRC detected. Please install manually:");
-            let content = generate(ServiceType::Openrc)?;
-            println!("\n{}\n", content);
-            println!("1. Save this to /etc/init.d/chitin");
-            println!("2. chmod +x /etc/init.d/chitin");
-            println!("3. rc-update add chitin default");
-            println!("4. rc-service chitin start");
-        }
-        None => anyhow::bail!(
-            "Unsupported OS or init system. Please use 'chitin service generate <type>' manually."
-        ),
+/// Runs service manager commands and file changes, or only prints them in dry-run mode
+pub struct Runner {
+    dry_run: bool,
+    /// What a dry run would have done, in order
+    planned: RefCell<Vec<String>>,
+}
+
+impl Runner {
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            planned: RefCell::new(Vec::new()),
+        }
     }
 
+    /// Print and remember a step skipped by a dry run
+    fn plan(&self, step: String) {
+        println!("[dry-run] {step}");
+        self.planned.borrow_mut().push(step);
+    }
+
+    /// Run a command that has to succeed, with its output going to the terminal
+    fn run(&self, program: &str, args: &[&str]) -> Result<()> {
+        if self.dry_run {
+            self.plan(format!("{program} {}", args.join(" ")));
+            return Ok(());
+        }
+        let status = Command::new(program)
+            .args(args)
+            .status()
+            .with_context(|| format!("Failed to run {program}"))?;
+        if !status.success() {
+            anyhow::bail!("{program} command failed: {:?}", args);
+        }
+        Ok(())
+    }
+
+    /// Run a command whose failure is expected and harmless, discarding its output
+    fn run_quiet(&self, program: &str, args: &[&str]) {
+        if self.dry_run {
+            self.plan(format!("{program} {}", args.join(" ")));
+            return;
+        }
+        let _ = Command::new(program)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+
+    /// Run a command and capture whether it succeeded and its stdout.
+    /// Returns `None` in dry-run mode or if the command could not be started.
+    fn query(&self, program: &str, args: &[&str]) -> Option<(bool, String)> {
+        if self.dry_run {
+            self.plan(format!("{program} {}", args.join(" ")));
+            return None;
+        }
+        let output = Command::new(program)
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        Some((
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ))
+    }
+
+    fn write_file(&self, path: &Path, content: &str, executable: bool) -> Result<()> {
+        let mode = if executable { 0o755 } else { 0o644 };
+        if self.dry_run {
+            self.plan(format!(
+                "write {} ({} bytes, mode {:o})",
+                path.display(),
+                content.len(),
+                mode
+            ));
+            return Ok(());
+        }
+        if let Some(dir) = path.parent()
+            && !dir.exists()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        println!("Wrote service file to {:?}", path);
+        Ok(())
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> Result<()> {
+        if self.dry_run {
+            self.plan(format!("link {} -> {}", link.display(), target.display()));
+            return Ok(());
+        }
+        if let Some(dir) = link.parent()
+            && !dir.exists()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+        if link.is_symlink() {
+            std::fs::remove_file(link)?;
+        }
+        std::os::unix::fs::symlink(target, link)
+            .with_context(|| format!("Failed to link {}", link.display()))?;
+        println!("Linked {:?} -> {:?}", link, target);
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        if self.dry_run {
+            self.plan(format!("remove {}", path.display()));
+            return Ok(());
+        }
+        match std::fs::remove_dir_all(path) {
+            Ok(()) => println!("Removed {:?}", path),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to remove {}", path.display()));
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        if self.dry_run {
+            self.plan(format!("remove {}", path.display()));
+            return Ok(());
+        }
+        match std::fs::remove_file(path) {
+            Ok(()) => println!("Removed {:?}", path),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to remove {}", path.display()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The explicitly requested service type, or the one detected for this system
+fn resolve(service_type: Option<ServiceType>) -> Result<ServiceType> {
+    service_type
+        .or_else(|| detect_service_type(std::env::consts::OS))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unsupported OS or init system. Please use 'chitin service generate <type>' manually."
+            )
+        })
+}
+
+/// The parts of this system a service gets installed into
+struct Host {
+    home: PathBuf,
+    config_dir: PathBuf,
+    data_dir: PathBuf,
+    /// Where the daemon logs by default, see `logging::default_dir`
+    log_dir: PathBuf,
+    /// runit's scan directory as set by $SVDIR
+    svdir: Option<PathBuf>,
+    /// Only looked up for OpenRC, since that runs `openrc --version`
+    openrc: Option<OpenrcTarget>,
+}
+
+impl Host {
+    fn detect(kind: &ServiceType) -> Result<Self> {
+        let base_dirs = BaseDirs::new().context("Could not determine base directories")?;
+        let openrc = match kind {
+            ServiceType::Openrc => Some(OpenrcTarget::detect(&base_dirs)?),
+            _ => None,
+        };
+        Ok(Self {
+            home: base_dirs.home_dir().to_path_buf(),
+            config_dir: base_dirs.config_dir().to_path_buf(),
+            data_dir: base_dirs.data_dir().to_path_buf(),
+            log_dir: logging::default_dir(),
+            svdir: env::var_os("SVDIR").map(PathBuf::from),
+            openrc,
+        })
+    }
+
+    fn openrc(&self) -> Result<&OpenrcTarget> {
+        self.openrc
+            .as_ref()
+            .context("OpenRC service details were not looked up")
+    }
+}
+
+/// What the generated service files start and listen on
+struct Daemon {
+    binary: String,
+    socket_path: String,
+}
+
+impl Daemon {
+    fn current() -> Result<Self> {
+        Ok(Self {
+            binary: env::current_exe()?
+                .canonicalize()?
+                .to_string_lossy()
+                .into_owned(),
+            socket_path: Config::load().server.socket_path,
+        })
+    }
+}
+
+pub fn install(runner: &Runner, service_type: Option<ServiceType>) -> Result<()> {
+    let kind = resolve(service_type)?;
+    install_on(runner, &Host::detect(&kind)?, &Daemon::current()?, kind)
+}
+
+fn install_on(runner: &Runner, host: &Host, daemon: &Daemon, kind: ServiceType) -> Result<()> {
+    match kind {
+        ServiceType::Launchd => install_launchd(runner, host, daemon),
+        ServiceType::Systemd => install_systemd(runner, host, daemon),
+        ServiceType::Openrc => install_openrc(runner, host, daemon),
+        kind @ (ServiceType::Runit | ServiceType::S6) => {
+            install_service_dir(runner, host, daemon, kind)
+        }
+    }
+}
+
+pub fn uninstall(runner: &Runner, service_type: Option<ServiceType>) -> Result<()> {
+    let kind = resolve(service_type)?;
+    uninstall_on(runner, &Host::detect(&kind)?, kind)
+}
+
+fn uninstall_on(runner: &Runner, host: &Host, kind: ServiceType) -> Result<()> {
+    match kind {
+        ServiceType::Launchd => {
+            let plist_path = launchd_plist_path(host);
+            let plist = plist_path.to_string_lossy();
+            runner.run_quiet("launchctl", &["unload", &plist]);
+            runner.remove_file(&plist_path)?;
+        }
+        ServiceType::Systemd => {
+            let systemd_dir = systemd_user_dir(host);
+            let mut args = vec!["--user", "disable", "--now"];
+            args.extend(SYSTEMD_UNITS);
+            runner.run_quiet("systemctl", &args);
+            for unit in SYSTEMD_UNITS {
+                runner.remove_file(&systemd_dir.join(unit))?;
+            }
+            runner.run("systemctl", &["--user", "daemon-reload"])?;
+        }
+        ServiceType::Openrc => {
+            let target = host.openrc()?;
+            runner.run_quiet("rc-service", &target.args(&[&target.service, "stop"]));
+            runner.run_quiet(
+                "rc-update",
+                &target.args(&["del", &target.service, "default"]),
+            );
+            runner.remove_file(&target.script)?;
+        }
+        kind @ (ServiceType::Runit | ServiceType::S6) => {
+            let dir = ServiceDir::new(&kind, host)?;
+            let link = dir.link.to_string_lossy();
+            runner.run_quiet(dir.control, &[dir.down, &link]);
+            runner.remove_file(&dir.link)?;
+            if let ServiceType::S6 = kind {
+                // Make s6-svscan drop the supervisor of the removed directory
+                let scandir = dir.scandir.to_string_lossy();
+                runner.run_quiet("s6-svscanctl", &["-an", &scandir]);
+            }
+            runner.remove_dir(&dir.staging)?;
+        }
+    }
+    println!("Service uninstalled.");
+    Ok(())
+}
+
+pub fn status(runner: &Runner, service_type: Option<ServiceType>) -> Result<()> {
+    let kind = resolve(service_type)?;
+    let host = Host::detect(&kind)?;
+    match kind {
+        ServiceType::Launchd => {
+            let installed = launchd_plist_path(&host).exists();
+            let Some((loaded, listing)) = runner.query("launchctl", &["list", LAUNCHD_LABEL])
+            else {
+                return Ok(());
+            };
+            // `launchctl list <label>` only has a PID entry while the job is running
+            let running = loaded && listing.contains("\"PID\" =");
+            println!("launchd agent {LAUNCHD_LABEL}");
+            println!("  installed: {}", yes_no(installed));
+            println!("  enabled:   {}", yes_no(loaded));
+            println!("  running:   {}", yes_no(running));
+        }
+        ServiceType::Systemd => {
+            for unit in SYSTEMD_UNITS {
+                let active = runner.query("systemctl", &["--user", "is-active", unit]);
+                let enabled = runner.query("systemctl", &["--user", "is-enabled", unit]);
+                if let (Some((_, active)), Some((_, enabled))) = (active, enabled) {
+                    println!("{unit}: {}, {}", or_unknown(&active), or_unknown(&enabled));
+                }
+            }
+        }
+        ServiceType::Openrc => {
+            let target = host.openrc()?;
+            let running = runner.query("rc-service", &target.args(&[&target.service, "status"]));
+            let enabled = runner.query("rc-update", &target.args(&["show", "default"]));
+            let (Some((running, _)), Some((_, runlevels))) = (running, enabled) else {
+                return Ok(());
+            };
+            let enabled = runlevels.lines().any(|l| {
+                l.split_whitespace()
+                    .next()
+                    .is_some_and(|name| name == target.service)
+            });
+            let kind = if target.run_as.is_some() {
+                "system"
+            } else {
+                "user"
+            };
+            println!("OpenRC {kind} service {}", target.service);
+            println!("  installed: {}", yes_no(target.script.exists()));
+            println!("  enabled:   {}", yes_no(enabled));
+            println!("  running:   {}", yes_no(running));
+        }
+        kind @ (ServiceType::Runit | ServiceType::S6) => {
+            let dir = ServiceDir::new(&kind, &host)?;
+            let link = dir.link.to_string_lossy();
+            let mut args = dir.status[1..].to_vec();
+            args.push(&link);
+            let Some((_, state)) = runner.query(dir.status[0], &args) else {
+                return Ok(());
+            };
+            println!("{kind:?} service {}", dir.link.display());
+            println!("  installed: {}", yes_no(dir.link.exists()));
+            println!("  state:     {}", or_unknown(&state));
+        }
+    }
     Ok(())
 }
 
+pub fn start(runner: &Runner, service_type: Option<ServiceType>) -> Result<()> {
+    let kind = resolve(service_type)?;
+    start_on(runner, &Host::detect(&kind)?, kind)
+}
+
+fn start_on(runner: &Runner, host: &Host, kind: ServiceType) -> Result<()> {
+    match kind {
+        ServiceType::Launchd => runner.run("launchctl", &["start", LAUNCHD_LABEL]),
+        ServiceType::Systemd => {
+            let mut args = vec!["--user", "start"];
+            args.extend(SYSTEMD_UNITS);
+            runner.run("systemctl", &args)
+        }
+        ServiceType::Openrc => {
+            let target = host.openrc()?;
+            runner.run("rc-service", &target.args(&[&target.service, "start"]))
+        }
+        kind @ (ServiceType::Runit | ServiceType::S6) => {
+            let dir = ServiceDir::new(&kind, host)?;
+            runner.run(dir.control, &[dir.up, &dir.link.to_string_lossy()])
+        }
+    }
+}
+
+pub fn stop(runner: &Runner, service_type: Option<ServiceType>) -> Result<()> {
+    let kind = resolve(service_type)?;
+    stop_on(runner, &Host::detect(&kind)?, kind)
+}
+
+fn stop_on(runner: &Runner, host: &Host, kind: ServiceType) -> Result<()> {
+    match kind {
+        ServiceType::Launchd => runner.run("launchctl", &["stop", LAUNCHD_LABEL]),
+        ServiceType::Systemd => {
+            let mut args = vec!["--user", "stop"];
+            args.extend(SYSTEMD_UNITS);
+            runner.run("systemctl", &args)
+        }
+        ServiceType::Openrc => {
+            let target = host.openrc()?;
+            runner.run("rc-service", &target.args(&[&target.service, "stop"]))
+        }
+        kind @ (ServiceType::Runit | ServiceType::S6) => {
+            let dir = ServiceDir::new(&kind, host)?;
+            runner.run(dir.control, &[dir.down, &dir.link.to_string_lossy()])
+        }
+    }
+}
+
+pub fn logs(
+    runner: &Runner,
+    service_type: Option<ServiceType>,
+    follow: bool,
+    lines: usize,
+) -> Result<()> {
+    let lines = lines.to_string();
+    // Options go first, since BSD tail stops parsing them at the first file
+    let mut args = vec!["-n".to_string(), lines];
+    if follow {
+        args.push("-f".to_string());
+    }
+    let service_type = resolve(service_type)?;
+    let host = Host::detect(&service_type)?;
+
+    // Where the daemon writes its log decides where to look, not the service manager
+    let log = Config::load().log;
+    let program = match (log.destination, service_type) {
+        (LogDestination::File, _) => {
+            let Some(file) = logging::current_file(&log) else {
+                anyhow::bail!(
+                    "No log file found at {} yet",
+                    logging::file_path(&log).display()
+                );
+            };
+            args.push(file.to_string_lossy().into_owned());
+            "tail"
+        }
+        (_, ServiceType::Systemd) => {
+            args.extend(["--user", "-u", "chitin.service"].map(String::from));
+            "journalctl"
+        }
+        (LogDestination::Journald, _) => {
+            args.extend(["-t", "chitin"].map(String::from));
+            "journalctl"
+        }
+        (_, ServiceType::Launchd) => {
+            args.push(launchd_log_path(&host).to_string_lossy().into_owned());
+            "tail"
+        }
+        (_, ServiceType::Openrc) => {
+            let target = host.openrc()?;
+            args.extend(
+                [&target.stdout_log, &target.stderr_log].map(|p| p.to_string_lossy().into_owned()),
+            );
+            "tail"
+        }
+        (_, kind @ (ServiceType::Runit | ServiceType::S6)) => {
+            let dir = ServiceDir::new(&kind, &host)?;
+            args.push(dir.log_file().to_string_lossy().into_owned());
+            "tail"
+        }
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    runner.run(program, &args)
+}
+
+fn or_unknown(state: &str) -> &str {
+    if state.is_empty() { "unknown" } else { state }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
 fn detect_service_type(os: &str) -> Option<ServiceType> {
     if os == "macos" {
         return Some(ServiceType::Launchd);
@@ -69,6 +566,12 @@ fn detect_service_type(os: &str) -> Option<ServiceType> {
         if std::path::Path::new("/sbin/openrc-run").exists() {
             return Some(ServiceType::Openrc);
         }
+        if std::path::Path::new("/run/runit").exists() || command_exists("runsvdir") {
+            return Some(ServiceType::Runit);
+        }
+        if std::path::Path::new("/run/s6").exists() || command_exists("s6-svscan") {
+            return Some(ServiceType::S6);
+        }
     }
     None
 }
@@ -81,114 +584,365 @@ fn command_exists(cmd: &str) -> bool {
         .unwrap_or(false)
 }
 
-fn install_launchd() -> Result<()> {
-    let content = generate(ServiceType::Launchd)?;
-    let base_dirs = BaseDirs::new().context("Could not determine base directories")?;
-    let launch_agents = base_dirs.home_dir().join("Library/LaunchAgents");
+fn launchd_plist_path(host: &Host) -> PathBuf {
+    host.home
+        .join("Library/LaunchAgents")
+        .join(format!("{LAUNCHD_LABEL}.plist"))
+}
+
+/// Where launchd writes the daemon's stdout and stderr
+fn launchd_log_path(host: &Host) -> PathBuf {
+    host.log_dir.join("launchd.log")
+}
+
+fn systemd_user_dir(host: &Host) -> PathBuf {
+    // For Systemd user units, standard path is ~/.config/systemd/user
+    host.config_dir.join("systemd/user")
+}
+
+fn install_launchd(runner: &Runner, host: &Host, daemon: &Daemon) -> Result<()> {
+    let content = take_content(generate_files(ServiceType::Launchd, host, daemon)?);
+    let plist_path = launchd_plist_path(host);
 
-    if !launch_agents.exists() {
-        std::fs::create_dir_all(&launch_agents)?;
+    // launchd creates the socket and log file but not their directories
+    if let Some(dir) = Path::new(&daemon.socket_path).parent()
+        && !runner.dry_run
+    {
+        socket::ensure_private_dir(dir)?;
+    }
+    if let Some(dir) = launchd_log_path(host).parent()
+        && !runner.dry_run
+    {
+        std::fs::create_dir_all(dir)?;
     }
-    let plist_path = launch_agents.join("com.user.chitin.plist");
 
-    std::fs::write(&plist_path, content)?;
-    println!("Wrote service file to {:?}", plist_path);
+    runner.write_file(&plist_path, &content, false)?;
 
     // Unload if exists (ignore error)
-    let _ = std::process::Command::new("launchctl")
-        .arg("unload")
-        .arg(&plist_path)
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status();
+    let plist = plist_path.to_string_lossy();
+    runner.run_quiet("launchctl", &["unload", &plist]);
 
     // Load
-    let status = std::process::Command::new("launchctl")
-        .arg("load")
-        .arg(&plist_path)
-        .status()
-        .context("Failed to run launchctl load")?;
+    runner
+        .run("launchctl", &["load", &plist])
+        .context("Failed to load service with launchctl")?;
 
-    if status.success() {
-        println!("Service installed and started successfully.");
-    } else {
-        anyhow::bail!("Failed to start service with launchctl");
+    println!("Service installed successfully. The daemon starts on the first request.");
+    Ok(())
+}
+
+fn install_systemd(runner: &Runner, host: &Host, daemon: &Daemon) -> Result<()> {
+    let files = generate_files(ServiceType::Systemd, host, daemon)?;
+    let systemd_dir = systemd_user_dir(host);
+
+    for file in files {
+        runner.write_file(&systemd_dir.join(file.name), &file.content, file.executable)?;
     }
+
+    // Daemon reload
+    runner.run("systemctl", &["--user", "daemon-reload"])?;
+
+    // Older installs enabled the service itself; the socket starts it on demand now
+    runner.run_quiet(
+        "systemctl",
+        &["--user", "disable", "--now", "chitin.service"],
+    );
+
+    // Enable and start listening
+    runner.run("systemctl", &["--user", "enable", "--now", "chitin.socket"])?;
+
+    println!("Service installed successfully. The daemon starts on the first request.");
     Ok(())
 }
 
-fn install_systemd() -> Result<()> {
-    let content = generate(ServiceType::Systemd)?;
-    let base_dirs = BaseDirs::new().context("Could not determine base directories")?;
-    // For Systemd user units, standard path is ~/.config/systemd/user
-    let config_dir = base_dirs.config_dir();
-    let systemd_dir = config_dir.join("systemd/user");
+/// Where the OpenRC service lives and how it is addressed
+struct OpenrcTarget {
+    /// Account to run the daemon as for a system service; `None` for a user service
+    run_as: Option<String>,
+    service: String,
+    script: PathBuf,
+    stdout_log: PathBuf,
+    stderr_log: PathBuf,
+}
 
-    if !systemd_dir.exists() {
-        std::fs::create_dir_all(&systemd_dir)?;
+impl OpenrcTarget {
+    /// A user service where OpenRC supports them, otherwise a per-user system service
+    fn detect(base_dirs: &BaseDirs) -> Result<Self> {
+        if openrc_supports_user_services() {
+            let log_dir = base_dirs
+                .state_dir()
+                .unwrap_or(base_dirs.data_dir())
+                .join("chitin");
+            return Ok(Self {
+                run_as: None,
+                service: "chitin".to_string(),
+                script: base_dirs.config_dir().join("rc/init.d/chitin"),
+                stdout_log: log_dir.join("chitin.log"),
+                stderr_log: log_dir.join("chitin.error.log"),
+            });
+        }
+
+        // Under sudo, install for the invoking user rather than root
+        let user = env::var("SUDO_USER")
+            .or_else(|_| env::var("USER"))
+            .ok()
+            .or_else(current_user_name)
+            .context("Could not determine the user to run the service as")?;
+        Ok(Self {
+            service: format!("chitin.{user}"),
+            script: PathBuf::from(format!("/etc/init.d/chitin.{user}")),
+            stdout_log: PathBuf::from(format!("/var/log/chitin.{user}.log")),
+            stderr_log: PathBuf::from(format!("/var/log/chitin.{user}.error.log")),
+            run_as: Some(user),
+        })
     }
 
-    let service_path = systemd_dir.join("chitin.service");
-    std::fs::write(&service_path, content)?;
-    println!("Wrote service file to {:?}", service_path);
+    /// Arguments for rc-service/rc-update, addressing user services where needed
+    fn args<'a>(&self, args: &[&'a str]) -> Vec<&'a str> {
+        let mut all = Vec::with_capacity(args.len() + 1);
+        if self.run_as.is_none() {
+            all.push("--user");
+        }
+        all.extend_from_slice(args);
+        all
+    }
+}
 
-    // Daemon reload
-    run_systemctl(&["--user", "daemon-reload"])?;
+fn current_user_name() -> Option<String> {
+    // SAFETY: getpwuid returns a pointer into static storage, which is copied
+    // out before any other passwd lookup could overwrite it
+    unsafe {
+        let entry = libc::getpwuid(socket::current_uid());
+        if entry.is_null() {
+            return None;
+        }
+        let name = std::ffi::CStr::from_ptr((*entry).pw_name);
+        Some(name.to_string_lossy().into_owned())
+    }
+}
+
+fn openrc_supports_user_services() -> bool {
+    let Ok(output) = Command::new("openrc").arg("--version").output() else {
+        return false;
+    };
+    // e.g. "openrc (OpenRC) 0.60.1"
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .filter_map(|word| {
+            let mut parts = word.split('.').map(|p| p.parse::<u32>().ok());
+            Some((parts.next()??, parts.next()??))
+        })
+        .next()
+        .is_some_and(|version| version >= OPENRC_USER_SERVICES_SINCE)
+}
 
-    // Enable and start
-    run_systemctl(&["--user", "enable", "--now", "chitin"])?;
+fn install_openrc(runner: &Runner, host: &Host, daemon: &Daemon) -> Result<()> {
+    let target = host.openrc()?;
+    if target.run_as.is_some() && socket::current_uid() != 0 && !runner.dry_run {
+        anyhow::bail!(
+            "This OpenRC has no user services, so chitin has to be installed as a system \
+             service. Run 'sudo chitin service install' to do that."
+        );
+    }
+
+    let content = take_content(generate_files(ServiceType::Openrc, host, daemon)?);
+    runner.write_file(&target.script, &content, true)?;
+    runner.run(
+        "rc-update",
+        &target.args(&["add", &target.service, "default"]),
+    )?;
+    runner.run("rc-service", &target.args(&[&target.service, "restart"]))?;
 
     println!("Service installed and started successfully.");
     Ok(())
 }
 
-fn run_systemctl(args: &[&str]) -> Result<()> {
-    let status = std::process::Command::new("systemctl")
-        .args(args)
-        .status()
-        .context("Failed to run systemctl")?;
+/// A service directory for a daemontools-style supervisor (runit or s6).
+///
+/// The files are written to a staging directory first and then linked into the
+/// scan directory, so the supervisor never sees a half-written service.
+struct ServiceDir {
+    staging: PathBuf,
+    scandir: PathBuf,
+    link: PathBuf,
+    control: &'static str,
+    /// Status command and its arguments before the service path
+    status: &'static [&'static str],
+    up: &'static str,
+    down: &'static str,
+}
 
-    if status.success() {
-        Ok(())
-    } else {
-        anyhow::bail!("systemctl command failed: {:?}", args);
+impl ServiceDir {
+    fn new(kind: &ServiceType, host: &Host) -> Result<Self> {
+        let (name, control, status, up, down) = match kind {
+            ServiceType::Runit => ("runit", "sv", &["sv", "status"][..], "up", "down"),
+            ServiceType::S6 => ("s6", "s6-svc", &["s6-svstat"][..], "-u", "-d"),
+            _ => anyhow::bail!("{kind:?} is not a service directory supervisor"),
+        };
+        // runit's sv honours $SVDIR; both conventionally use ~/service for user services
+        let scandir = match (kind, &host.svdir) {
+            (ServiceType::Runit, Some(dir)) => dir.clone(),
+            _ => host.home.join("service"),
+        };
+        Ok(Self {
+            staging: host.data_dir.join("chitin").join(name).join("chitin"),
+            link: scandir.join("chitin"),
+            scandir,
+            control,
+            status,
+            up,
+            down,
+        })
+    }
+
+    fn log_file(&self) -> PathBuf {
+        self.staging.join("log/main/current")
     }
 }
 
-pub fn generate(service_type: ServiceType) -> Result<String> {
-    let binary_path = env::current_exe()?
-        .canonicalize()?
-        .to_string_lossy()
-        .into_owned();
+fn install_service_dir(
+    runner: &Runner,
+    host: &Host,
+    daemon: &Daemon,
+    kind: ServiceType,
+) -> Result<()> {
+    let dir = ServiceDir::new(&kind, host)?;
+    let supervised = dir.scandir.exists();
+
+    for file in generate_files(kind.clone(), host, daemon)? {
+        runner.write_file(&dir.staging.join(file.name), &file.content, file.executable)?;
+    }
+    runner.symlink(&dir.staging, &dir.link)?;
+    if let ServiceType::S6 = kind {
+        // Pick up the new directory now rather than on the next scan
+        let scandir = dir.scandir.to_string_lossy();
+        runner.run_quiet("s6-svscanctl", &["-a", &scandir]);
+    }
+
+    println!("Service installed successfully.");
+    if !supervised {
+        let supervisor = match kind {
+            ServiceType::S6 => "s6-svscan",
+            _ => "runsvdir",
+        };
+        println!(
+            "Note: {} did not exist before. Make sure '{supervisor} {}' runs for your user, \
+             e.g. from a system service or your session startup.",
+            dir.scandir.display(),
+            dir.scandir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Content of a service type that generates a single file
+fn take_content(files: Vec<ServiceFile>) -> String {
+    files
+        .into_iter()
+        .next()
+        .map(|file| file.content)
+        .unwrap_or_default()
+}
+
+/// A file produced by `generate`, e.g. a unit file or a launchd plist
+pub struct ServiceFile {
+    pub name: &'static str,
+    pub content: String,
+    pub executable: bool,
+}
+
+pub fn generate(service_type: ServiceType) -> Result<Vec<ServiceFile>> {
+    let host = Host::detect(&service_type)?;
+    generate_files(service_type, &host, &Daemon::current()?)
+}
+
+fn generate_files(
+    service_type: ServiceType,
+    host: &Host,
+    daemon: &Daemon,
+) -> Result<Vec<ServiceFile>> {
+    let binary_path = &daemon.binary;
+    let socket_path = &daemon.socket_path;
 
     match service_type {
-        ServiceType::Launchd => generate_launchd(&binary_path),
-        ServiceType::Systemd => generate_systemd(&binary_path),
-        ServiceType::Openrc => generate_openrc(&binary_path),
+        ServiceType::Launchd => Ok(vec![ServiceFile {
+            name: "com.user.chitin.plist",
+            content: generate_launchd(binary_path, socket_path, &launchd_log_path(host))?,
+            executable: false,
+        }]),
+        ServiceType::Systemd => Ok(vec![
+            ServiceFile {
+                name: "chitin.service",
+                content: generate_systemd(binary_path)?,
+                executable: false,
+            },
+            ServiceFile {
+                name: "chitin.socket",
+                content: generate_systemd_socket(socket_path)?,
+                executable: false,
+            },
+        ]),
+        ServiceType::Openrc => Ok(vec![ServiceFile {
+            name: "chitin",
+            content: generate_openrc(binary_path, host.openrc()?)?,
+            executable: true,
+        }]),
+        ServiceType::Runit => Ok(vec![
+            ServiceFile {
+                name: "run",
+                content: generate_supervised_run(binary_path)?,
+                executable: true,
+            },
+            ServiceFile {
+                name: "log/run",
+                content: generate_supervised_log_run("svlogd -tt main")?,
+                executable: true,
+            },
+        ]),
+        ServiceType::S6 => Ok(vec![
+            ServiceFile {
+                name: "run",
+                content: generate_supervised_run(binary_path)?,
+                executable: true,
+            },
+            ServiceFile {
+                name: "log/run",
+                content: generate_supervised_log_run("s6-log T main")?,
+                executable: true,
+            },
+        ]),
     }
 }
 
-fn generate_launchd(binary_path: &str) -> Result<String> {
+fn generate_launchd(binary_path: &str, socket_path: &str, log_path: &Path) -> Result<String> {
+    let log_path = log_path.display();
+    // launchd listens on the socket and starts the daemon on the first connection
     Ok(format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
 <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
 <plist version="1.0">
 <dict>
     <key>Label</key>
-    <string>com.user.chitin</string>
+    <string>{LAUNCHD_LABEL}</string>
     <key>ProgramArguments</key>
     <array>
         <string>{binary_path}</string>
         <string>daemon</string>
     </array>
-    <key>KeepAlive</key>
-    <true/>
-    <key>RunAtLoad</key>
-    <true/>
+    <key>Sockets</key>
+    <dict>
+        <key>Listeners</key>
+        <dict>
+            <key>SockPathName</key>
+            <string>{socket_path}</string>
+            <key>SockPathMode</key>
+            <integer>384</integer>
+        </dict>
+    </dict>
     <key>StandardOutPath</key>
-    <string>/tmp/chitin.log</string>
+    <string>{log_path}</string>
     <key>StandardErrorPath</key>
-    <string>/tmp/chitin.error.log</string>
+    <string>{log_path}</string>
 </dict>
 </plist>
 "#
@@ -200,21 +954,51 @@ fn generate_systemd(binary_path: &str) -> Result<String> {
         r#"[Unit]
 Description=Chitin AI Shell Assistant Daemon
 Documentation=https://github.com/chitin-ai/chitin
+Requires=chitin.socket
+After=chitin.socket
 
 [Service]
 ExecStart={binary_path} daemon
-Restart=always
+Restart=on-failure
 RestartSec=5
 Type=simple
+"#
+    ))
+}
+
+fn generate_systemd_socket(socket_path: &str) -> Result<String> {
+    // %t is $XDG_RUNTIME_DIR for user units, so keep the unit portable when possible
+    let default_path = socket::default_path();
+    let listen = if Path::new(socket_path) == default_path
+        && let Ok(runtime_dir) = env::var("XDG_RUNTIME_DIR")
+        && let Ok(rest) = default_path.strip_prefix(runtime_dir)
+    {
+        format!("%t/{}", rest.to_string_lossy())
+    } else {
+        socket_path.to_string()
+    };
+
+    Ok(format!(
+        r#"[Unit]
+Description=Chitin AI Shell Assistant Socket
+Documentation=https://github.com/chitin-ai/chitin
+
+[Socket]
+ListenStream={listen}
+SocketMode=0600
+DirectoryMode=0700
+RemoveOnStop=true
 
 [Install]
-WantedBy=default.target
+WantedBy=sockets.target
 "#
     ))
 }
 
-fn generate_openrc(binary_path: &str) -> Result<String> {
-    Ok(format!(
+fn generate_openrc(binary_path: &str, target: &OpenrcTarget) -> Result<String> {
+    let stdout_log = target.stdout_log.display();
+    let stderr_log = target.stderr_log.display();
+    let mut script = format!(
         r#"#!/sbin/openrc-run
 
 name="chitin"
@@ -222,11 +1006,336 @@ description="Chitin AI Shell Assistant Daemon"
 command="{binary_path}"
 command_args="daemon"
 command_background=true
-pidfile="/run/chitin.pid"
+pidfile="${{RC_SVCDIR}}/${{RC_SVCNAME}}.pid"
+output_log="{stdout_log}"
+error_log="{stderr_log}"
+"#
+    );
+
+    match &target.run_as {
+        // A user service already runs as the user, inside their session
+        None => script.push_str(
+            r#"
+start_pre() {
+    checkpath --directory --mode 0700 "$(dirname "$output_log")"
+}
+"#,
+        ),
+        Some(user) => script.push_str(&format!(
+            r#"command_user="{user}"
+
+start_pre() {{
+    checkpath --file --owner "{user}" --mode 0600 "$output_log" "$error_log"
+}}
 
 depend() {{
-    need net
+    after net
 }}
+"#
+        )),
+    }
+    Ok(script)
+}
+
+fn generate_supervised_run(binary_path: &str) -> Result<String> {
+    Ok(format!(
+        r#"#!/bin/sh
+exec 2>&1
+exec {binary_path} daemon
+"#
+    ))
+}
+
+fn generate_supervised_log_run(logger: &str) -> Result<String> {
+    Ok(format!(
+        r#"#!/bin/sh
+mkdir -p main
+exec {logger}
 "#
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINARY: &str = "/usr/local/bin/chitin";
+
+    fn dry_run(action: impl FnOnce(&Runner) -> Result<()>) -> Vec<String> {
+        let runner = Runner::new(true);
+        action(&runner).expect("dry run");
+        runner.planned.into_inner()
+    }
+
+    /// A host with fixed directories and an OpenRC user service
+    fn host() -> Host {
+        Host {
+            home: PathBuf::from("/home/me"),
+            config_dir: PathBuf::from("/home/me/.config"),
+            data_dir: PathBuf::from("/home/me/.local/share"),
+            log_dir: PathBuf::from("/home/me/.local/state/chitin"),
+            svdir: None,
+            openrc: Some(OpenrcTarget {
+                run_as: None,
+                service: "chitin".to_string(),
+                script: PathBuf::from("/home/me/.config/rc/init.d/chitin"),
+                stdout_log: PathBuf::from("/home/me/.local/state/chitin/chitin.log"),
+                stderr_log: PathBuf::from("/home/me/.local/state/chitin/chitin.error.log"),
+            }),
+        }
+    }
+
+    fn daemon() -> Daemon {
+        Daemon {
+            binary: BINARY.to_string(),
+            socket_path: "/home/me/.chitin/chitin.sock".to_string(),
+        }
+    }
+
+    #[test]
+    fn launchd_plist_listens_on_the_socket() {
+        let plist = generate_launchd(
+            BINARY,
+            "/Users/me/Library/Caches/chitin/chitin.sock",
+            Path::new("/Users/me/Library/Logs/chitin/launchd.log"),
+        )
+        .unwrap();
+        assert!(plist.contains("<string>com.user.chitin</string>"));
+        assert!(plist.contains(&format!(
+            "<string>{BINARY}</string>\n        <string>daemon</string>"
+        )));
+        assert!(plist.contains(
+            "<key>SockPathName</key>\n            <string>/Users/me/Library/Caches/chitin/chitin.sock</string>"
+        ));
+        // 0600
+        assert!(plist.contains("<key>SockPathMode</key>\n            <integer>384</integer>"));
+        assert!(plist.contains(
+            "<key>StandardErrorPath</key>\n    <string>/Users/me/Library/Logs/chitin/launchd.log</string>"
+        ));
+    }
+
+    #[test]
+    fn systemd_units_are_socket_activated() {
+        let service = generate_systemd(BINARY).unwrap();
+        assert!(service.contains(&format!("ExecStart={BINARY} daemon\n")));
+        assert!(service.contains("Requires=chitin.socket\n"));
+
+        let socket = generate_systemd_socket("/srv/chitin.sock").unwrap();
+        assert!(socket.contains("ListenStream=/srv/chitin.sock\n"));
+        assert!(socket.contains("SocketMode=0600\n"));
+        assert!(socket.contains("WantedBy=sockets.target\n"));
+    }
+
+    #[test]
+    fn openrc_scripts_for_user_and_system_services() {
+        let user = OpenrcTarget {
+            run_as: None,
+            service: "chitin".to_string(),
+            script: PathBuf::from("/home/me/.config/rc/init.d/chitin"),
+            stdout_log: PathBuf::from("/home/me/.local/state/chitin/chitin.log"),
+            stderr_log: PathBuf::from("/home/me/.local/state/chitin/chitin.error.log"),
+        };
+        let script = generate_openrc(BINARY, &user).unwrap();
+        assert!(script.starts_with("#!/sbin/openrc-run\n"));
+        assert!(script.contains(&format!("command=\"{BINARY}\"\n")));
+        assert!(script.contains("output_log=\"/home/me/.local/state/chitin/chitin.log\"\n"));
+        assert!(script.contains("checkpath --directory --mode 0700"));
+        assert!(!script.contains("command_user"));
+        assert_eq!(
+            user.args(&["chitin", "start"]),
+            ["--user", "chitin", "start"]
+        );
+
+        let system = OpenrcTarget {
+            run_as: Some("me".to_string()),
+            service: "chitin.me".to_string(),
+            script: PathBuf::from("/etc/init.d/chitin.me"),
+            stdout_log: PathBuf::from("/var/log/chitin.me.log"),
+            stderr_log: PathBuf::from("/var/log/chitin.me.error.log"),
+        };
+        let script = generate_openrc(BINARY, &system).unwrap();
+        assert!(script.contains("command_user=\"me\"\n"));
+        assert!(script.contains("checkpath --file --owner \"me\" --mode 0600"));
+        assert_eq!(system.args(&["chitin.me", "start"]), ["chitin.me", "start"]);
+    }
+
+    #[test]
+    fn supervised_run_scripts() {
+        assert_eq!(
+            generate_supervised_run(BINARY).unwrap(),
+            format!("#!/bin/sh\nexec 2>&1\nexec {BINARY} daemon\n")
+        );
+        assert_eq!(
+            generate_supervised_log_run("svlogd -tt main").unwrap(),
+            "#!/bin/sh\nmkdir -p main\nexec svlogd -tt main\n"
+        );
+    }
+
+    #[test]
+    fn dry_run_systemd() {
+        let (host, daemon) = (host(), daemon());
+        assert_eq!(
+            dry_run(|r| install_on(r, &host, &daemon, ServiceType::Systemd)),
+            [
+                "write /home/me/.config/systemd/user/chitin.service (239 bytes, mode 644)",
+                "write /home/me/.config/systemd/user/chitin.socket (242 bytes, mode 644)",
+                "systemctl --user daemon-reload",
+                "systemctl --user disable --now chitin.service",
+                "systemctl --user enable --now chitin.socket",
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| uninstall_on(r, &host, ServiceType::Systemd)),
+            [
+                "systemctl --user disable --now chitin.socket chitin.service",
+                "remove /home/me/.config/systemd/user/chitin.socket",
+                "remove /home/me/.config/systemd/user/chitin.service",
+                "systemctl --user daemon-reload",
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| start_on(r, &host, ServiceType::Systemd)),
+            ["systemctl --user start chitin.socket chitin.service"]
+        );
+        assert_eq!(
+            dry_run(|r| stop_on(r, &host, ServiceType::Systemd)),
+            ["systemctl --user stop chitin.socket chitin.service"]
+        );
+    }
+
+    #[test]
+    fn dry_run_launchd() {
+        let (host, daemon) = (host(), daemon());
+        let plist = "/home/me/Library/LaunchAgents/com.user.chitin.plist";
+        assert_eq!(
+            dry_run(|r| install_on(r, &host, &daemon, ServiceType::Launchd)),
+            [
+                format!("write {plist} (841 bytes, mode 644)"),
+                format!("launchctl unload {plist}"),
+                format!("launchctl load {plist}"),
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| uninstall_on(r, &host, ServiceType::Launchd)),
+            [
+                format!("launchctl unload {plist}"),
+                format!("remove {plist}")
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| start_on(r, &host, ServiceType::Launchd)),
+            ["launchctl start com.user.chitin"]
+        );
+        assert_eq!(
+            dry_run(|r| stop_on(r, &host, ServiceType::Launchd)),
+            ["launchctl stop com.user.chitin"]
+        );
+    }
+
+    #[test]
+    fn dry_run_openrc() {
+        let (mut host, daemon) = (host(), daemon());
+        let script = "/home/me/.config/rc/init.d/chitin";
+        assert_eq!(
+            dry_run(|r| install_on(r, &host, &daemon, ServiceType::Openrc)),
+            [
+                format!("write {script} (393 bytes, mode 755)"),
+                "rc-update --user add chitin default".to_string(),
+                "rc-service --user chitin restart".to_string(),
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| uninstall_on(r, &host, ServiceType::Openrc)),
+            [
+                "rc-service --user chitin stop".to_string(),
+                "rc-update --user del chitin default".to_string(),
+                format!("remove {script}"),
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| start_on(r, &host, ServiceType::Openrc)),
+            ["rc-service --user chitin start"]
+        );
+
+        host.openrc = Some(OpenrcTarget {
+            run_as: Some("me".to_string()),
+            service: "chitin.me".to_string(),
+            script: PathBuf::from("/etc/init.d/chitin.me"),
+            stdout_log: PathBuf::from("/var/log/chitin.me.log"),
+            stderr_log: PathBuf::from("/var/log/chitin.me.error.log"),
+        });
+        assert_eq!(
+            dry_run(|r| install_on(r, &host, &daemon, ServiceType::Openrc)),
+            [
+                "write /etc/init.d/chitin.me (413 bytes, mode 755)",
+                "rc-update add chitin.me default",
+                "rc-service chitin.me restart",
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| stop_on(r, &host, ServiceType::Openrc)),
+            ["rc-service chitin.me stop"]
+        );
+    }
+
+    #[test]
+    fn dry_run_runit() {
+        let (mut host, daemon) = (host(), daemon());
+        let staging = "/home/me/.local/share/chitin/runit/chitin";
+        assert_eq!(
+            dry_run(|r| install_on(r, &host, &daemon, ServiceType::Runit)),
+            [
+                format!("write {staging}/run (54 bytes, mode 755)"),
+                format!("write {staging}/log/run (45 bytes, mode 755)"),
+                format!("link /home/me/service/chitin -> {staging}"),
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| uninstall_on(r, &host, ServiceType::Runit)),
+            [
+                "sv down /home/me/service/chitin".to_string(),
+                "remove /home/me/service/chitin".to_string(),
+                format!("remove {staging}"),
+            ]
+        );
+
+        // sv looks for services in $SVDIR
+        host.svdir = Some(PathBuf::from("/var/service"));
+        assert_eq!(
+            dry_run(|r| start_on(r, &host, ServiceType::Runit)),
+            ["sv up /var/service/chitin"]
+        );
+        assert_eq!(
+            dry_run(|r| stop_on(r, &host, ServiceType::Runit)),
+            ["sv down /var/service/chitin"]
+        );
+    }
+
+    #[test]
+    fn dry_run_s6() {
+        let (host, daemon) = (host(), daemon());
+        let staging = "/home/me/.local/share/chitin/s6/chitin";
+        assert_eq!(
+            dry_run(|r| install_on(r, &host, &daemon, ServiceType::S6)),
+            [
+                format!("write {staging}/run (54 bytes, mode 755)"),
+                format!("write {staging}/log/run (43 bytes, mode 755)"),
+                format!("link /home/me/service/chitin -> {staging}"),
+                "s6-svscanctl -a /home/me/service".to_string(),
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| uninstall_on(r, &host, ServiceType::S6)),
+            [
+                "s6-svc -d /home/me/service/chitin".to_string(),
+                "remove /home/me/service/chitin".to_string(),
+                "s6-svscanctl -an /home/me/service".to_string(),
+                format!("remove {staging}"),
+            ]
+        );
+        assert_eq!(
+            dry_run(|r| start_on(r, &host, ServiceType::S6)),
+            ["s6-svc -u /home/me/service/chitin"]
+        );
+    }
+}
@@ -1,38 +1,121 @@
+use crate::config::{ContextScope, SessionConfig};
 use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct SessionStore {
     sessions: HashMap<String, Session>,
     max_history: usize,
+    max_sessions: usize,
+    /// Sessions idle for longer than this are dropped; `None` keeps them
+    ttl: Option<Duration>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone)]
 pub struct Session {
     prompts: VecDeque<String>,
     last_command: Option<String>,
+    last_used: Instant,
 }
 
-impl SessionStore {
-    pub fn new(max_history: usize) -> Self {
+impl Default for Session {
+    fn default() -> Self {
         Self {
-            sessions: HashMap::new(),
-            max_history,
+            prompts: VecDeque::new(),
+            last_command: None,
+            last_used: Instant::now(),
         }
     }
+}
+
+impl SessionStore {
+    pub fn new(config: &SessionConfig) -> Self {
+        let mut store = Self {
+            sessions: HashMap::new(),
+            max_history: 0,
+            max_sessions: 0,
+            ttl: None,
+        };
+        store.configure(config);
+        store
+    }
+
+    /// Apply new limits; existing sessions are trimmed on the next `evict`
+    pub fn configure(&mut self, config: &SessionConfig) {
+        self.max_history = config.max_history;
+        self.max_sessions = config.max_sessions.max(1);
+        self.ttl = (config.ttl_secs > 0).then(|| Duration::from_secs(config.ttl_secs));
+    }
 
     pub fn record_input(&mut self, session_id: &str, prompt: &str) {
-        let session = self.sessions.entry(session_id.to_string()).or_default();
+        let max_history = self.max_history;
+        let session = self.touch(session_id);
         session.prompts.push_back(prompt.to_string());
-        while session.prompts.len() > self.max_history {
+        while session.prompts.len() > max_history {
             session.prompts.pop_front();
         }
     }
 
     pub fn record_output(&mut self, session_id: &str, command: &str) {
-        let session = self.sessions.entry(session_id.to_string()).or_default();
+        let session = self.touch(session_id);
         session.last_command = Some(command.to_string());
     }
 
+    fn touch(&mut self, session_id: &str) -> &mut Session {
+        let session = self.sessions.entry(session_id.to_string()).or_default();
+        session.last_used = Instant::now();
+        session
+    }
+
+    /// Drop idle sessions, then the least recently used ones beyond the limit
+    pub fn evict(&mut self) {
+        if let Some(ttl) = self.ttl {
+            self.sessions
+                .retain(|_, session| session.last_used.elapsed() <= ttl);
+        }
+
+        let excess = self.sessions.len().saturating_sub(self.max_sessions);
+        if excess > 0 {
+            let mut by_age: Vec<(Instant, String)> = self
+                .sessions
+                .iter()
+                .map(|(id, session)| (session.last_used, id.clone()))
+                .collect();
+            by_age.sort();
+            for (_, id) in by_age.into_iter().take(excess) {
+                self.sessions.remove(&id);
+            }
+        }
+
+        let max_history = self.max_history;
+        for session in self.sessions.values_mut() {
+            while session.prompts.len() > max_history {
+                session.prompts.pop_front();
+            }
+        }
+    }
+
+    /// Forget the history of a session. Returns whether it existed.
+    pub fn reset(&mut self, session_id: &str) -> bool {
+        self.sessions.remove(session_id).is_some()
+    }
+
+    /// Start `to` with a copy of the history of `from`, replacing anything in `to`.
+    ///
+    /// Returns the number of prompts copied.
+    pub fn fork(&mut self, from: &str, to: &str) -> usize {
+        let mut session = self.sessions.get(from).cloned().unwrap_or_default();
+        session.last_used = Instant::now();
+        let copied = session.prompts.len();
+        self.sessions.insert(to.to_string(), session);
+        copied
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
     pub fn snapshot(&self, session_id: &str) -> SessionSnapshot {
         let session = self.sessions.get(session_id);
         SessionSnapshot {
@@ -44,8 +127,158 @@ impl SessionStore {
     }
 }
 
+/// Key in the store whose history applies to a request from `session_id` in `pwd`
+pub fn scope_key(scope: ContextScope, session_id: &str, pwd: &str) -> String {
+    match scope {
+        ContextScope::Session => session_id.to_string(),
+        ContextScope::Project => {
+            let pwd = Path::new(pwd);
+            // `.git` is a file in worktrees and submodules
+            let root = pwd
+                .ancestors()
+                .find(|dir| dir.join(".git").exists())
+                .unwrap_or(pwd);
+            format!("project:{}", root.display())
+        }
+        ContextScope::Directory => format!("dir:{pwd}"),
+        ContextScope::Global => "global".to_string(),
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct SessionSnapshot {
     pub history: Vec<String>,
     pub last_command: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_store(max_sessions: usize, ttl_secs: u64) -> SessionStore {
+        SessionStore::new(&SessionConfig {
+            ttl_secs,
+            max_sessions,
+            max_history: 2,
+            ..SessionConfig::default()
+        })
+    }
+
+    /// Pretend `session_id` was last used `secs` seconds ago
+    fn age(store: &mut SessionStore, session_id: &str, secs: u64) {
+        store.sessions.get_mut(session_id).unwrap().last_used =
+            Instant::now() - Duration::from_secs(secs);
+    }
+
+    #[test]
+    fn idle_sessions_expire() {
+        let mut store = new_store(10, 60);
+        store.record_input("idle", "p");
+        store.record_input("active", "p");
+        age(&mut store, "idle", 61);
+        age(&mut store, "active", 59);
+        store.evict();
+        assert!(store.snapshot("idle").history.is_empty());
+        assert_eq!(store.snapshot("active").history, ["p"]);
+
+        // 0 keeps them however old
+        let mut store = new_store(10, 0);
+        store.record_input("idle", "p");
+        age(&mut store, "idle", 365 * 24 * 60 * 60);
+        store.evict();
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn least_recently_used_go_first() {
+        let mut store = new_store(2, 0);
+        for (session_id, secs) in [("a", 30), ("b", 20), ("c", 10)] {
+            store.record_input(session_id, "p");
+            age(&mut store, session_id, secs);
+        }
+        // Using "a" again makes "b" the oldest
+        store.record_output("a", "ls");
+        store.evict();
+        assert_eq!(store.len(), 2);
+        assert!(store.snapshot("b").history.is_empty());
+        assert_eq!(store.snapshot("a").last_command.as_deref(), Some("ls"));
+        assert_eq!(store.snapshot("c").history, ["p"]);
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut store = new_store(10, 0);
+        for prompt in ["one", "two", "three"] {
+            store.record_input("s", prompt);
+        }
+        assert_eq!(store.snapshot("s").history, ["two", "three"]);
+    }
+
+    #[test]
+    fn reset_forgets_history() {
+        let mut store = new_store(10, 0);
+        store.record_input("s", "p");
+        assert!(store.reset("s"));
+        assert!(!store.reset("s"));
+        assert!(store.snapshot("s").history.is_empty());
+    }
+
+    #[test]
+    fn fork_copies_history() {
+        let mut store = new_store(10, 0);
+        store.record_input("parent", "one");
+        store.record_output("parent", "ls");
+        store.record_input("child", "replaced");
+        assert_eq!(store.fork("parent", "child"), 1);
+
+        let child = store.snapshot("child");
+        assert_eq!(child.history, ["one"]);
+        assert_eq!(child.last_command.as_deref(), Some("ls"));
+
+        // Each goes on on its own
+        store.record_input("child", "two");
+        store.record_input("parent", "other");
+        assert_eq!(store.snapshot("parent").history, ["one", "other"]);
+        assert_eq!(store.snapshot("child").history, ["one", "two"]);
+
+        assert_eq!(store.fork("missing", "fresh"), 0);
+        assert!(store.snapshot("fresh").history.is_empty());
+    }
+
+    #[test]
+    fn scope_keys() {
+        let key = |scope| scope_key(scope, "me@pts/1:42", "/home/me/src");
+        assert_eq!(key(ContextScope::Session), "me@pts/1:42");
+        assert_eq!(key(ContextScope::Directory), "dir:/home/me/src");
+        assert_eq!(key(ContextScope::Global), "global");
+        assert_eq!(
+            scope_key(ContextScope::Directory, "other", "/home/me/src"),
+            key(ContextScope::Directory)
+        );
+    }
+
+    #[test]
+    fn project_scope_finds_the_repository_root() {
+        let base = std::env::temp_dir().join(format!("chitin-scope-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let repo = base.join("repo");
+        let nested = repo.join("src/deep");
+        let worktree = base.join("worktree");
+        let outside = base.join("plain/dir");
+        for dir in [&nested, &worktree.join("src"), &outside, &repo.join(".git")] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        // Worktrees and submodules have a `.git` file instead
+        std::fs::write(worktree.join(".git"), "gitdir: /elsewhere\n").unwrap();
+
+        let key = |pwd: &Path| scope_key(ContextScope::Project, "s", &pwd.display().to_string());
+        let project = |root: &Path| format!("project:{}", root.display());
+        assert_eq!(key(&repo), project(&repo));
+        assert_eq!(key(&nested), project(&repo));
+        assert_eq!(key(&worktree.join("src")), project(&worktree));
+        // Outside a repository the directory itself is the project
+        assert_eq!(key(&outside), project(&outside));
+
+        std::fs::remove_dir_all(base).unwrap();
+    }
+}
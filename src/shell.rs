@@ -1,52 +1,440 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
 use directories::BaseDirs;
-use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
 const ZSH_SCRIPT: &str = include_str!("../shell/chitin.zsh");
+const BASH_SCRIPT: &str = include_str!("../shell/chitin.bash");
+const FISH_SCRIPT: &str = include_str!("../shell/chitin.fish");
+const NU_SCRIPT: &str = include_str!("../shell/chitin.nu");
+const PWSH_SCRIPT: &str = include_str!("../shell/chitin.ps1");
 
-pub fn install() -> Result<()> {
-    // 1. Determine install location (XDG Data Home or similar)
-    let base_dirs = BaseDirs::new().context("Could not determine base directories")?;
-    // Use ~/.local/share/chitin/chitin.zsh (Linux/macOS standard)
-    let data_dir = base_dirs.data_dir().join("chitin");
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Shell {
+    Zsh,
+    Bash,
+    Fish,
+    Nu,
+    Pwsh,
+}
+
+impl Shell {
+    /// The user's login shell, from `$SHELL`
+    pub fn detect() -> Option<Self> {
+        let shell = std::env::var("SHELL").ok()?;
+        match Path::new(&shell).file_name()?.to_str()? {
+            "zsh" => Some(Self::Zsh),
+            "bash" => Some(Self::Bash),
+            "fish" => Some(Self::Fish),
+            "nu" => Some(Self::Nu),
+            "pwsh" => Some(Self::Pwsh),
+            _ => None,
+        }
+    }
+
+    /// Name sent to the daemon as `shell` in `chitin.input`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Zsh => "zsh",
+            Self::Bash => "bash",
+            Self::Fish => "fish",
+            Self::Nu => "nu",
+            Self::Pwsh => "pwsh",
+        }
+    }
 
-    if !data_dir.exists() {
-        std::fs::create_dir_all(&data_dir)?;
+    fn script(self) -> &'static str {
+        match self {
+            Self::Zsh => ZSH_SCRIPT,
+            Self::Bash => BASH_SCRIPT,
+            Self::Fish => FISH_SCRIPT,
+            Self::Nu => NU_SCRIPT,
+            Self::Pwsh => PWSH_SCRIPT,
+        }
     }
 
-    let script_path = data_dir.join("chitin.zsh");
+    fn script_name(self) -> &'static str {
+        match self {
+            Self::Zsh => "chitin.zsh",
+            Self::Bash => "chitin.bash",
+            Self::Fish => "chitin.fish",
+            Self::Nu => "chitin.nu",
+            Self::Pwsh => "chitin.ps1",
+        }
+    }
+
+    /// File the plugin is sourced from, for shells that do not load it by themselves
+    fn rc_file(self, base_dirs: &BaseDirs) -> Option<PathBuf> {
+        let home_dir = base_dirs.home_dir();
+        match self {
+            Self::Zsh => {
+                let dir = std::env::var_os("ZDOTDIR")
+                    .filter(|dir| !dir.is_empty())
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| home_dir.to_path_buf());
+                Some(dir.join(".zshrc"))
+            }
+            Self::Bash => Some(home_dir.join(".bashrc")),
+            Self::Pwsh => {
+                Some(xdg_config_dir(base_dirs).join("powershell/Microsoft.PowerShell_profile.ps1"))
+            }
+            Self::Fish | Self::Nu => None,
+        }
+    }
+
+    /// Directory whose scripts the shell loads at startup
+    fn autoload_dir(self, base_dirs: &BaseDirs) -> PathBuf {
+        match self {
+            Self::Fish => xdg_config_dir(base_dirs).join("fish/conf.d"),
+            // Nushell uses the platform config dir, like BaseDirs
+            Self::Nu => base_dirs.config_dir().join("nushell/autoload"),
+            _ => base_dirs.data_dir().join("chitin"),
+        }
+    }
+
+    fn script_path(self, base_dirs: &BaseDirs) -> PathBuf {
+        self.autoload_dir(base_dirs).join(self.script_name())
+    }
+
+    fn source_line(self, script_path: &Path) -> String {
+        match self {
+            Self::Pwsh => format!(". \"{}\"", script_path.to_string_lossy()),
+            _ => format!("source \"{}\"", script_path.to_string_lossy()),
+        }
+    }
+}
+
+/// fish and PowerShell use ~/.config on macOS too, not the platform config dir
+fn xdg_config_dir(base_dirs: &BaseDirs) -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| base_dirs.home_dir().join(".config"))
+}
+
+/// Lines around what `install` adds to an rc file, so it can be found again
+const BEGIN_MARKER: &str = "# >>> chitin shell integration >>>";
+const END_MARKER: &str = "# <<< chitin shell integration <<<";
+/// Header written by versions that appended to the rc file without markers
+const LEGACY_HEADER: &str = "# Chitin Shell Integration";
+
+fn resolve_shell(shell: Option<Shell>) -> Result<Shell> {
+    shell.or_else(Shell::detect).ok_or_else(|| {
+        anyhow!(
+            "Could not detect a supported shell from $SHELL. Use --shell <zsh|bash|fish|nu|pwsh>."
+        )
+    })
+}
+
+/// Print the plugin, for `eval "$(chitin shell init zsh)"` and the like
+pub fn init(shell: Shell) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(init_script(shell).as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The plugin as `init` prints it.
+///
+/// The bash plugin returns early in non-interactive shells. Under `eval` in
+/// a bashrc that would return from the bashrc itself, so it runs inside a
+/// function there instead.
+fn init_script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => {
+            format!("_chitin_init() {{\n{BASH_SCRIPT}}}\n_chitin_init\nunset -f _chitin_init\n")
+        }
+        _ => shell.script().to_string(),
+    }
+}
+
+/// Where oh-my-zsh looks for custom plugins: `$ZSH_CUSTOM/plugins/chitin`
+fn oh_my_zsh_plugin_dir(base_dirs: &BaseDirs) -> PathBuf {
+    let var = |name| std::env::var_os(name).filter(|dir| !dir.is_empty());
+    let custom = var("ZSH_CUSTOM")
+        .map(PathBuf::from)
+        .or_else(|| var("ZSH").map(|zsh| PathBuf::from(zsh).join("custom")))
+        .unwrap_or_else(|| base_dirs.home_dir().join(".oh-my-zsh/custom"));
+    custom.join("plugins/chitin")
+}
+
+fn oh_my_zsh_script_path(base_dirs: &BaseDirs) -> PathBuf {
+    oh_my_zsh_plugin_dir(base_dirs).join("chitin.plugin.zsh")
+}
+
+/// Install as an oh-my-zsh custom plugin, which oh-my-zsh sources itself once
+/// it is listed in `plugins=(...)`
+fn install_oh_my_zsh(base_dirs: &BaseDirs) -> Result<()> {
+    let script_path = oh_my_zsh_script_path(base_dirs);
+    let plugin_dir = oh_my_zsh_plugin_dir(base_dirs);
+    if !plugin_dir.parent().is_some_and(Path::exists) {
+        println!(
+            "Warning: {:?} does not exist. Is oh-my-zsh installed? Set ZSH_CUSTOM if it lives elsewhere.",
+            plugin_dir.parent().unwrap_or(&plugin_dir)
+        );
+    }
+    std::fs::create_dir_all(&plugin_dir)?;
     std::fs::write(&script_path, ZSH_SCRIPT)?;
+    println!("Installed oh-my-zsh plugin to {:?}", script_path);
+    println!("Add chitin to plugins=(...) in your .zshrc and restart your shell to activate.");
+    Ok(())
+}
+
+pub fn install(shell: Option<Shell>, oh_my_zsh: bool) -> Result<()> {
+    let base_dirs = BaseDirs::new().context("Could not determine base directories")?;
+    if oh_my_zsh {
+        return install_oh_my_zsh(&base_dirs);
+    }
+    let shell = resolve_shell(shell)?;
+
+    // 1. Determine install location: the shell's own autoload directory, or
+    // ~/.local/share/chitin (XDG Data Home) for scripts sourced from an rc file
+    let script_path = shell.script_path(&base_dirs);
+    if let Some(dir) = script_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&script_path, shell.script())?;
     println!("Installed shell plugin to {:?}", script_path);
 
-    // 2. Update .zshrc
-    let home_dir = base_dirs.home_dir();
-    let zshrc_path = home_dir.join(".zshrc");
+    // 2. Update the rc file
+    let Some(rc_path) = shell.rc_file(&base_dirs) else {
+        println!("Please restart your shell to activate.");
+        return Ok(());
+    };
 
-    if !zshrc_path.exists() {
-        // If no .zshrc, we might warn or create it. Creating is safer if user intends to use Zsh.
-        // But let's check if it exists first.
-        println!("Warning: No ~/.zshrc found. Creating one.");
-        std::fs::File::create(&zshrc_path)?;
+    let content = read_rc(&rc_path)?;
+    let updated = add_block(&content, &shell.source_line(&script_path))
+        .with_context(|| format!("Failed to update {:?}", rc_path))?;
+    if updated == content {
+        println!("Shell plugin already sourced in {:?}", rc_path);
+        return Ok(());
     }
+    write_rc(&rc_path, &updated)?;
+
+    println!("Added source line to {:?}", rc_path);
+    println!(
+        "Please restart your shell or source {:?} to activate.",
+        rc_path
+    );
 
-    let source_line = format!("source \"{}\"", script_path.to_string_lossy());
+    Ok(())
+}
 
-    // Check if already present
-    let content = std::fs::read_to_string(&zshrc_path)?;
-    if content.contains(&source_line) {
-        println!("Shell plugin already sourced in {:?}", zshrc_path);
+pub fn uninstall(shell: Option<Shell>, oh_my_zsh: bool) -> Result<()> {
+    let base_dirs = BaseDirs::new().context("Could not determine base directories")?;
+    if oh_my_zsh {
+        let plugin_dir = oh_my_zsh_plugin_dir(&base_dirs);
+        match std::fs::remove_dir_all(&plugin_dir) {
+            Ok(()) => println!("Removed oh-my-zsh plugin {:?}", plugin_dir),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                println!("oh-my-zsh plugin not installed at {:?}", plugin_dir)
+            }
+            Err(err) => return Err(err).context(format!("Failed to remove {:?}", plugin_dir)),
+        }
+        println!("Remove chitin from plugins=(...) in your .zshrc to finish.");
         return Ok(());
     }
+    let shell = resolve_shell(shell)?;
+    let script_path = shell.script_path(&base_dirs);
 
-    // Append
-    let mut file = OpenOptions::new().append(true).open(&zshrc_path)?;
+    if let Some(rc_path) = shell.rc_file(&base_dirs) {
+        let content = read_rc(&rc_path)?;
+        let rest = remove_block(&content, &shell.source_line(&script_path))
+            .with_context(|| format!("Failed to update {:?}", rc_path))?;
+        if rest == content {
+            println!("Shell plugin not sourced in {:?}", rc_path);
+        } else {
+            write_rc(&rc_path, &rest)?;
+            println!("Removed source line from {:?}", rc_path);
+        }
+    }
 
-    writeln!(file, "\n# Chitin Shell Integration")?;
-    writeln!(file, "{}", source_line)?;
+    match std::fs::remove_file(&script_path) {
+        Ok(()) => println!("Removed shell plugin {:?}", script_path),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            println!("Shell plugin not installed at {:?}", script_path)
+        }
+        Err(err) => return Err(err).context(format!("Failed to remove {:?}", script_path)),
+    }
+    println!("Please restart your shell to finish.");
 
-    println!("Added source line to {:?}", zshrc_path);
-    println!("Please restart your shell or run 'source ~/.zshrc' to activate.");
+    Ok(())
+}
 
+/// Rewrite installed plugins that differ from the ones built into this binary
+pub fn update() -> Result<()> {
+    let base_dirs = BaseDirs::new().context("Could not determine base directories")?;
+    let mut installed = 0;
+    let plugins = Shell::value_variants()
+        .iter()
+        .map(|shell| (shell.script_path(&base_dirs), shell.script()))
+        .chain([(oh_my_zsh_script_path(&base_dirs), ZSH_SCRIPT)]);
+    for (script_path, script) in plugins {
+        let Ok(current) = std::fs::read_to_string(&script_path) else {
+            continue;
+        };
+        installed += 1;
+        if current == script {
+            println!("Shell plugin {:?} is up to date", script_path);
+        } else {
+            std::fs::write(&script_path, script)?;
+            println!("Updated shell plugin {:?}", script_path);
+        }
+    }
+    if installed == 0 {
+        return Err(anyhow!(
+            "No shell plugin installed. Run 'chitin shell install' first."
+        ));
+    }
     Ok(())
 }
+
+fn read_rc(path: &Path) -> Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(content),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(err).context(format!("Failed to read {:?}", path)),
+    }
+}
+
+/// The rc file with the marked block sourcing the plugin at its end, moved
+/// there from wherever an earlier install put it
+fn add_block(content: &str, source_line: &str) -> Result<String> {
+    let block = format!("{BEGIN_MARKER}\n{source_line}\n{END_MARKER}\n");
+    if content.contains(&block) {
+        return Ok(content.to_string());
+    }
+    let rest = remove_block(content, source_line)?;
+    Ok(if rest.is_empty() {
+        block
+    } else {
+        format!("{rest}\n{block}")
+    })
+}
+
+/// Drop the marked block, and the unmarked lines older versions added.
+///
+/// A begin marker without an end marker after it is an error rather than a
+/// reason to drop everything below it.
+fn remove_block(content: &str, source_line: &str) -> Result<String> {
+    let mut kept = Vec::new();
+    let mut block_start = None;
+    for (number, line) in content.lines().enumerate() {
+        match line.trim_end() {
+            BEGIN_MARKER if block_start.is_none() => block_start = Some(number + 1),
+            END_MARKER if block_start.is_some() => block_start = None,
+            LEGACY_HEADER => {}
+            line if line == source_line => {}
+            _ if block_start.is_some() => {}
+            _ => kept.push(line),
+        }
+    }
+    if let Some(line) = block_start {
+        bail!("{BEGIN_MARKER:?} on line {line} has no {END_MARKER:?} after it; remove it by hand");
+    }
+    let rest = kept.join("\n");
+    let rest = rest.trim_end();
+    Ok(if rest.is_empty() {
+        String::new()
+    } else {
+        format!("{rest}\n")
+    })
+}
+
+/// Replace an rc file without ever leaving a half written one behind.
+///
+/// The previous version is kept next to it as `<name>.chitin.bak`. Symlinked
+/// rc files (as dotfile managers set up) are followed, so the link survives.
+fn write_rc(path: &Path, content: &str) -> Result<()> {
+    let path = match std::fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(err) => return Err(err).context(format!("Failed to resolve {:?}", path)),
+    };
+    let dir = path.parent().context("rc file has no parent directory")?;
+    std::fs::create_dir_all(dir)?;
+    let name = path
+        .file_name()
+        .context("rc file has no file name")?
+        .to_string_lossy()
+        .into_owned();
+
+    let permissions = match std::fs::metadata(&path) {
+        Ok(metadata) => {
+            let backup = dir.join(format!("{name}.chitin.bak"));
+            std::fs::copy(&path, &backup)
+                .with_context(|| format!("Failed to back up {:?}", path))?;
+            println!("Backed up {:?} to {:?}", path, backup);
+            Some(metadata.permissions())
+        }
+        Err(_) => None,
+    };
+
+    let temp = dir.join(format!(".{name}.chitin.tmp"));
+    let mut file = std::fs::File::create(&temp)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    if let Some(permissions) = permissions {
+        file.set_permissions(permissions)?;
+    }
+    drop(file);
+    std::fs::rename(&temp, &path).with_context(|| format!("Failed to replace {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "source ~/.config/chitin/chitin.zsh";
+
+    #[test]
+    fn bash_init_keeps_the_interactive_guard() {
+        let script = init_script(Shell::Bash);
+        assert!(script.contains("if [[ $- != *i* ]] || (( BASH_VERSINFO[0] < 4 )); then"));
+        assert!(script.starts_with("_chitin_init() {\n"));
+        assert!(script.ends_with("}\n_chitin_init\nunset -f _chitin_init\n"));
+    }
+
+    #[test]
+    fn removes_marked_block() {
+        let content =
+            format!("export A=1\n{BEGIN_MARKER}\n{SOURCE}\n{END_MARKER}\nalias ll='ls -l'\n");
+        assert_eq!(
+            remove_block(&content, SOURCE).unwrap(),
+            "export A=1\nalias ll='ls -l'\n"
+        );
+    }
+
+    #[test]
+    fn keeps_file_without_end_marker() {
+        let content = format!("export A=1\n{BEGIN_MARKER}\n{SOURCE}\nalias ll='ls -l'\n");
+        assert!(remove_block(&content, SOURCE).is_err());
+        assert!(add_block(&content, SOURCE).is_err());
+    }
+
+    #[test]
+    fn removes_legacy_lines() {
+        let content = format!("export A=1\n\n{LEGACY_HEADER}\n{SOURCE}\n");
+        assert_eq!(remove_block(&content, SOURCE).unwrap(), "export A=1\n");
+        assert_eq!(
+            add_block(&content, SOURCE).unwrap(),
+            format!("export A=1\n\n{BEGIN_MARKER}\n{SOURCE}\n{END_MARKER}\n")
+        );
+    }
+
+    #[test]
+    fn reinstall_is_idempotent() {
+        let installed = add_block("export A=1\n", SOURCE).unwrap();
+        assert_eq!(
+            installed,
+            format!("export A=1\n\n{BEGIN_MARKER}\n{SOURCE}\n{END_MARKER}\n")
+        );
+        assert_eq!(add_block(&installed, SOURCE).unwrap(), installed);
+        assert_eq!(
+            add_block("", SOURCE).unwrap(),
+            add_block(&add_block("", SOURCE).unwrap(), SOURCE).unwrap()
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{Context, Result, bail};
+#[cfg(not(target_os = "macos"))]
+use std::env;
+use std::fs;
+use std::os::fd::FromRawFd;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::UnixListener as StdUnixListener;
+use std::path::{Path, PathBuf};
+use tokio::net::{UnixListener, UnixStream};
+
+pub fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions and cannot fail
+    unsafe { libc::getuid() }
+}
+
+/// Per-user directory for the socket and other runtime files.
+///
+/// Uses `$XDG_RUNTIME_DIR/chitin`, falling back to `/tmp/chitin-<uid>` where
+/// there is no runtime dir.
+#[cfg(not(target_os = "macos"))]
+pub fn runtime_dir() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("chitin"),
+        _ => fallback_dir(),
+    }
+}
+
+/// Per-user directory for the socket and other runtime files.
+///
+/// macOS has no runtime dir and empties /tmp on reboot, while launchd needs
+/// the socket's directory to exist already, so this is `~/Library/Caches/chitin`.
+#[cfg(target_os = "macos")]
+pub fn runtime_dir() -> PathBuf {
+    fallback_dir()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn fallback_dir() -> PathBuf {
+    PathBuf::from("/tmp").join(format!("chitin-{}", current_uid()))
+}
+
+#[cfg(target_os = "macos")]
+fn fallback_dir() -> PathBuf {
+    directories::BaseDirs::new()
+        .map(|base_dirs| base_dirs.cache_dir().join("chitin"))
+        .unwrap_or_else(|| PathBuf::from("/tmp").join(format!("chitin-{}", current_uid())))
+}
+
+pub fn default_path() -> PathBuf {
+    runtime_dir().join("chitin.sock")
+}
+
+/// Where a daemon started without `$XDG_RUNTIME_DIR` listens by default
+pub fn fallback_path() -> PathBuf {
+    fallback_dir().join("chitin.sock")
+}
+
+/// Get the socket path ready for binding.
+///
+/// Creates its directory with 0700 permissions if needed and removes a stale
+/// socket file, but refuses to touch one that a live daemon still listens on.
+pub fn prepare(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        ensure_private_dir(dir)?;
+    }
+
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    if !metadata.file_type().is_socket() {
+        bail!(
+            "{} exists and is not a socket; refusing to remove it",
+            path.display()
+        );
+    }
+    if is_live(path) {
+        bail!(
+            "another chitin daemon is already listening on {}",
+            path.display()
+        );
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Whether a daemon is accepting connections on `path`
+pub fn is_live(path: &Path) -> bool {
+    std::os::unix::net::UnixStream::connect(path).is_ok()
+}
+
+/// Bind the listening socket, readable and writable by the owner only
+pub fn bind(path: &Path) -> Result<UnixListener> {
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("failed to bind {}", path.display()))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Listening socket handed over by the service manager, if we were socket activated.
+///
+/// Supports systemd's `LISTEN_FDS` protocol and launchd's `Sockets` key.
+pub fn inherited() -> Result<Option<UnixListener>> {
+    let Some(listener) = inherited_std()? else {
+        return Ok(None);
+    };
+    listener.set_nonblocking(true)?;
+    Ok(Some(UnixListener::from_std(listener)?))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn inherited_std() -> Result<Option<StdUnixListener>> {
+    const SD_LISTEN_FDS_START: libc::c_int = 3;
+
+    // LISTEN_PID guards against picking up variables meant for our parent
+    let pid = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|p| p.parse::<u32>().ok());
+    let fds = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<i32>().ok())
+        .unwrap_or(0);
+    if pid != Some(std::process::id()) || fds < 1 {
+        return Ok(None);
+    }
+    if fds > 1 {
+        bail!("expected a single socket from systemd, got {fds}");
+    }
+
+    // systemd passes the descriptor without close-on-exec; keep it out of child processes
+    // SAFETY: fcntl on a descriptor we own has no memory safety requirements
+    unsafe {
+        libc::fcntl(SD_LISTEN_FDS_START, libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+    // SAFETY: systemd hands ownership of the first passed descriptor to us
+    Ok(Some(unsafe {
+        StdUnixListener::from_raw_fd(SD_LISTEN_FDS_START)
+    }))
+}
+
+#[cfg(target_os = "macos")]
+fn inherited_std() -> Result<Option<StdUnixListener>> {
+    // From <launch.h>, available since macOS 10.10
+    unsafe extern "C" {
+        fn launch_activate_socket(
+            name: *const libc::c_char,
+            fds: *mut *mut libc::c_int,
+            cnt: *mut libc::size_t,
+        ) -> libc::c_int;
+    }
+
+    let mut fds: *mut libc::c_int = std::ptr::null_mut();
+    let mut count: libc::size_t = 0;
+    // SAFETY: the name is a NUL-terminated static string and both out pointers
+    // point to live locals. On success launchd stores a malloc'd array of
+    // `count` descriptors in `fds`, which we own; on failure it stores nothing.
+    let err = unsafe { launch_activate_socket(c"Listeners".as_ptr(), &mut fds, &mut count) };
+    if err != 0 {
+        // Not started by launchd, or the plist declares no sockets
+        return Ok(None);
+    }
+    // SAFETY: with `count > 0`, `fds` points to at least one initialized descriptor
+    let fd = (count > 0).then(|| unsafe { *fds });
+    // SAFETY: `fds` came from malloc in launch_activate_socket (or is still null,
+    // which free accepts), is freed exactly once, and is not read afterwards.
+    // Freeing the array does not close the descriptors in it.
+    unsafe { libc::free(fds.cast()) };
+    // SAFETY: launchd hands ownership of the activated descriptors to us, and the
+    // plist declares a single listener, so nothing else owns or closes this one
+    Ok(fd.map(|fd| unsafe { StdUnixListener::from_raw_fd(fd) }))
+}
+
+/// User id of the process on the other end of `stream`, from SO_PEERCRED
+pub fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    stream.peer_cred().ok().map(|cred| cred.uid())
+}
+
+pub fn ensure_private_dir(dir: &Path) -> Result<()> {
+    if !dir.exists() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        return Ok(());
+    }
+
+    // Only tighten directories we own; shared ones like /tmp are left alone
+    if dir != runtime_dir() {
+        return Ok(());
+    }
+    let metadata = fs::metadata(dir)?;
+    if metadata.uid() != current_uid() {
+        bail!(
+            "{} is owned by another user (uid {}); refusing to use it",
+            dir.display(),
+            metadata.uid()
+        );
+    }
+    if metadata.mode() & 0o077 != 0 {
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
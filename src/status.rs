@@ -0,0 +1,167 @@
+use crate::client;
+use crate::config::{Config, mask_secret};
+use crate::protocol::METHOD_STATUS;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Counters collected by the daemon while serving requests
+#[derive(Debug)]
+pub struct Stats {
+    started: Instant,
+    requests: AtomicU64,
+    errors: AtomicU64,
+    provider_errors: AtomicU64,
+    generations: AtomicU64,
+    generation_micros: AtomicU64,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            provider_errors: AtomicU64::new(0),
+            generations: AtomicU64::new(0),
+            generation_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_generation(&self, elapsed: Duration, ok: bool) {
+        if !ok {
+            self.provider_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.generations.fetch_add(1, Ordering::Relaxed);
+        self.generation_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn average_latency_ms(&self) -> f64 {
+        let count = self.generations.load(Ordering::Relaxed);
+        if count == 0 {
+            return 0.0;
+        }
+        self.generation_micros.load(Ordering::Relaxed) as f64 / count as f64 / 1000.0
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusReport {
+    pub pid: u32,
+    pub uptime_secs: u64,
+    pub socket_path: String,
+    pub config_path: Option<String>,
+    pub provider: ProviderStatus,
+    pub sessions: usize,
+    pub requests: u64,
+    pub errors: u64,
+    pub provider_errors: u64,
+    pub avg_latency_ms: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProviderStatus {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub model: String,
+    pub api_base: Option<String>,
+    pub api_key: Option<String>,
+}
+
+impl StatusReport {
+    pub fn collect(config: &Config, model: &str, sessions: usize, stats: &Stats) -> Self {
+        let openai = &config.provider.openai;
+        Self {
+            pid: std::process::id(),
+            uptime_secs: stats.uptime().as_secs(),
+            socket_path: config.server.socket_path.clone(),
+            config_path: config
+                .source
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned()),
+            provider: ProviderStatus {
+                type_: config.provider.type_.clone(),
+                model: model.to_string(),
+                api_base: openai.api_base.clone(),
+                api_key: openai.api_key.as_deref().map(mask_secret),
+            },
+            sessions,
+            requests: stats.requests.load(Ordering::Relaxed),
+            errors: stats.errors.load(Ordering::Relaxed),
+            provider_errors: stats.provider_errors.load(Ordering::Relaxed),
+            avg_latency_ms: stats.average_latency_ms(),
+        }
+    }
+}
+
+pub async fn run(json: bool) -> Result<()> {
+    let response = client::call(METHOD_STATUS, serde_json::json!({})).await?;
+    if let Some(error) = response.error {
+        return Err(anyhow!("daemon returned error: {}", error.message));
+    }
+    let result = response
+        .result
+        .ok_or_else(|| anyhow!("daemon returned an empty status"))?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    let report: StatusReport = serde_json::from_value(result)?;
+    println!("Chitin daemon is running");
+    println!("  pid:             {}", report.pid);
+    println!("  uptime:          {}", format_uptime(report.uptime_secs));
+    println!("  socket:          {}", report.socket_path);
+    println!(
+        "  config:          {}",
+        report
+            .config_path
+            .as_deref()
+            .unwrap_or("(defaults / environment)")
+    );
+    println!("  provider:        {}", report.provider.type_);
+    println!("  model:           {}", report.provider.model);
+    if let Some(base) = &report.provider.api_base {
+        println!("  api base:        {base}");
+    }
+    println!(
+        "  api key:         {}",
+        report.provider.api_key.as_deref().unwrap_or("(not set)")
+    );
+    println!("  sessions:        {}", report.sessions);
+    println!("  requests:        {}", report.requests);
+    println!("  errors:          {}", report.errors);
+    println!("  provider errors: {}", report.provider_errors);
+    println!("  avg latency:     {:.1} ms", report.avg_latency_ms);
+    Ok(())
+}
+
+fn format_uptime(secs: u64) -> String {
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (hours, rem) = (rem / 3_600, rem % 3_600);
+    let (minutes, seconds) = (rem / 60, rem % 60);
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
@@ -0,0 +1,350 @@
+use std::fmt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::warn;
+
+/// How long a syntax check may take before it is given up on
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Names that need no lookup, separated by whitespace
+
+/// Reserved words that can start a command in POSIX-like shells
+const KEYWORDS: &str = "! { } [[ case coproc for function if select time until while";
+
+const BASH_BUILTINS: &str = "\
+    . : [ alias bg bind break builtin caller cd command compgen complete compopt continue \
+    declare dirs disown echo enable eval exec exit export false fc fg getopts hash help \
+    history jobs kill let local logout mapfile popd printf pushd pwd read readarray readonly \
+    return set shift shopt source suspend test times trap true type typeset ulimit umask \
+    unalias unset wait";
+
+/// Zsh builtins on top of the ones it shares with Bash
+const ZSH_BUILTINS: &str = "\
+    autoload bindkey bye chdir disable emulate float foreach functions integer limit noglob \
+    nocorrect print private pushln r rehash repeat sched setopt unfunction unhash unlimit \
+    unsetopt vared whence where which zcompile zformat zle zmodload zparseopts zstyle";
+
+const FISH_BUILTINS: &str = "\
+    . : [ abbr and argparse begin bg bind block break builtin cd command commandline \
+    complete contains continue count disown echo eval exec exit false fg for function \
+    functions history if jobs math not or path printf pwd random read realpath return set \
+    set_color source status string switch test time true type ulimit wait while";
+
+/// What a generated command is checked against: the user's shell rather
+/// than the daemon's
+pub struct Environment<'a> {
+    pub shell: Option<&'a str>,
+    pub pwd: &'a str,
+    /// `PATH` of the client; the daemon's own is used when missing
+    pub path: Option<&'a str>,
+    /// Aliases and functions defined in the user's shell
+    pub aliases: &'a [String],
+}
+
+/// Reason not to hand a generated command back as is
+#[derive(Debug)]
+pub enum Problem {
+    Syntax(String),
+    UnknownCommand(String),
+}
+
+impl Problem {
+    /// What kind of problem this is, without any part of the command
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Problem::Syntax(_) => "syntax error",
+            Problem::UnknownCommand(_) => "unknown command",
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Syntax(message) => write!(f, "it does not parse: {message}"),
+            Problem::UnknownCommand(name) => {
+                write!(f, "`{name}` is not an installed command, builtin or alias")
+            }
+        }
+    }
+}
+
+/// Check that a generated command parses and that the command it starts
+/// with exists
+pub async fn check(env: &Environment<'_>, command: &str) -> Result<(), Problem> {
+    if let Some(shell) = env.shell {
+        check_syntax(shell, command)
+            .await
+            .map_err(Problem::Syntax)?;
+    }
+    if let Some(name) = first_word(command)
+        && !resolves_blocking(env, name).await
+    {
+        return Err(Problem::UnknownCommand(name.to_string()));
+    }
+    Ok(())
+}
+
+/// `resolves` on the blocking pool, since it stats every `PATH` entry
+async fn resolves_blocking(env: &Environment<'_>, name: &str) -> bool {
+    let shell = env.shell.map(str::to_string);
+    let pwd = env.pwd.to_string();
+    let path = env.path.map(str::to_string);
+    let aliases = env.aliases.to_vec();
+    let name = name.to_string();
+    tokio::task::spawn_blocking(move || {
+        let env = Environment {
+            shell: shell.as_deref(),
+            pwd: &pwd,
+            path: path.as_deref(),
+            aliases: &aliases,
+        };
+        resolves(&env, &name)
+    })
+    .await
+    // Not being able to look is no reason to reject the command
+    .unwrap_or(true)
+}
+
+/// Parse a generated command with the target shell's no-exec mode.
+///
+/// Returns the parser's complaint if it does not parse. Shells without such a
+/// mode, or not installed on this machine, are not checked. The parser runs
+/// with an empty environment, no config files and no stdin, and is killed if
+/// it takes too long.
+async fn check_syntax(shell: &str, command: &str) -> Result<(), String> {
+    let args: &[&str] = match shell {
+        "zsh" => &["-f", "-n", "-c"],
+        "bash" => &["--norc", "--noprofile", "-n", "-c"],
+        "fish" => &["--no-config", "-n", "-c"],
+        _ => return Ok(()),
+    };
+
+    let child = Command::new(shell)
+        .args(args)
+        .arg(command)
+        .env_clear()
+        .env("LC_ALL", "C")
+        .current_dir("/")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            warn!(
+                "Chitin: failed to run {} for a syntax check: {}",
+                shell, err
+            );
+            return Ok(());
+        }
+    };
+
+    match tokio::time::timeout(CHECK_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) if output.status.success() => Ok(()),
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.trim();
+            if message.is_empty() {
+                Err(format!("{shell} could not parse the command"))
+            } else {
+                Err(message.to_string())
+            }
+        }
+        Ok(Err(err)) => {
+            warn!("Chitin: syntax check with {} failed: {}", shell, err);
+            Ok(())
+        }
+        Err(_) => {
+            warn!("Chitin: syntax check with {} timed out", shell);
+            Ok(())
+        }
+    }
+}
+
+/// The command a generated line starts with, when that can be told without
+/// expanding anything
+fn first_word(command: &str) -> Option<&str> {
+    for word in command.split_whitespace() {
+        // Leading VAR=value assignments
+        if let Some((name, _)) = word.split_once('=')
+            && !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            continue;
+        }
+        let word = word
+            .split([';', '|', '&', '<', '>', ')'])
+            .next()
+            .unwrap_or_default();
+        if word.is_empty() || word.contains(['$', '`', '"', '\'', '(', '*', '?', '\\', '~']) {
+            return None;
+        }
+        return Some(word);
+    }
+    None
+}
+
+fn resolves(env: &Environment<'_>, name: &str) -> bool {
+    if name.contains('/') {
+        return is_executable(&Path::new(env.pwd).join(name));
+    }
+
+    let builtins: &[&str] = match env.shell {
+        Some("bash") => &[KEYWORDS, BASH_BUILTINS],
+        Some("zsh") => &[KEYWORDS, BASH_BUILTINS, ZSH_BUILTINS],
+        Some("fish") => &[FISH_BUILTINS],
+        // Commands are not looked up the same way there
+        Some("nu" | "pwsh" | "powershell") => return true,
+        _ => &[KEYWORDS, BASH_BUILTINS, ZSH_BUILTINS, FISH_BUILTINS],
+    };
+    let builtin = builtins
+        .iter()
+        .any(|list| list.split_whitespace().any(|word| word == name));
+    if builtin || env.aliases.iter().any(|alias| alias == name) {
+        return true;
+    }
+
+    let path = match env.path {
+        Some(path) => path.to_string(),
+        None => std::env::var("PATH").unwrap_or_default(),
+    };
+    // Empty and relative entries are relative to where the user is
+    std::env::split_paths(&path).any(|dir| is_executable(&Path::new(env.pwd).join(dir).join(name)))
+}
+
+fn is_executable(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A fresh directory with an executable `tool` in `bin` and a
+    /// non-executable `notes` next to it
+    fn fixture(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("chitin-validate-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(dir.join("bin/tool"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(dir.join("bin/tool"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(dir.join("bin/notes"), "").unwrap();
+        dir
+    }
+
+    fn env<'a>(
+        shell: Option<&'a str>,
+        pwd: &'a str,
+        path: &'a str,
+        aliases: &'a [String],
+    ) -> Environment<'a> {
+        Environment {
+            shell,
+            pwd,
+            path: Some(path),
+            aliases,
+        }
+    }
+
+    #[test]
+    fn first_word_of_commands() {
+        for (command, expected) in [
+            ("ls -la", Some("ls")),
+            ("VAR=x cmd --flag", Some("cmd")),
+            ("A=1 B=2 cmd", Some("cmd")),
+            ("cmd|grep foo", Some("cmd")),
+            ("cmd;other", Some("cmd")),
+            ("cmd>out.txt", Some("cmd")),
+            ("./script.sh arg", Some("./script.sh")),
+            ("$(which ls) -l", None),
+            ("`which ls`", None),
+            ("$EDITOR file", None),
+            ("'ls' -l", None),
+            ("\"ls\" -l", None),
+            ("~/bin/tool", None),
+            ("VAR=x", None),
+            ("", None),
+        ] {
+            assert_eq!(first_word(command), expected, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn builtins_per_shell() {
+        let none = [];
+        for (shell, name, expected) in [
+            (Some("bash"), "cd", true),
+            (Some("bash"), "if", true),
+            (Some("bash"), "bindkey", false),
+            (Some("zsh"), "bindkey", true),
+            (Some("zsh"), "export", true),
+            (Some("fish"), "set_color", true),
+            (Some("fish"), "export", false),
+            (Some("nu"), "anything-at-all", true),
+            (None, "set_color", true),
+            (None, "no-such-command-here", false),
+        ] {
+            let env = env(shell, "/", "", &none);
+            assert_eq!(resolves(&env, name), expected, "{shell:?} {name}");
+        }
+    }
+
+    #[test]
+    fn aliases_resolve() {
+        let aliases = ["ll".to_string(), "gs".to_string()];
+        let env = env(Some("bash"), "/", "", &aliases);
+        assert!(resolves(&env, "ll"));
+        assert!(!resolves(&env, "la"));
+    }
+
+    #[test]
+    fn path_lookup() {
+        let dir = fixture("path");
+        let bin = dir.join("bin").display().to_string();
+        let pwd = dir.display().to_string();
+        let none = [];
+        for (path, pwd, name, expected) in [
+            (bin.as_str(), "/", "tool", true),
+            (bin.as_str(), "/", "notes", false),
+            ("/nonexistent", "/", "tool", false),
+            // Relative entries are taken from the user's directory
+            ("bin", pwd.as_str(), "tool", true),
+            // and so is an empty one
+            ("/nonexistent::", bin.as_str(), "tool", true),
+            ("/nonexistent:", "/", "tool", false),
+        ] {
+            let env = env(Some("bash"), pwd, path, &none);
+            assert_eq!(
+                resolves(&env, name),
+                expected,
+                "PATH={path:?} in {pwd} for {name}"
+            );
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn relative_paths() {
+        let dir = fixture("relative");
+        let pwd = dir.display().to_string();
+        let none = [];
+        let env = env(Some("bash"), &pwd, "", &none);
+        assert!(resolves(&env, "./bin/tool"));
+        assert!(resolves(&env, "bin/tool"));
+        assert!(!resolves(&env, "./bin/notes"));
+        assert!(!resolves(&env, "./bin/missing"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
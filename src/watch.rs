@@ -0,0 +1,160 @@
+use crate::DaemonState;
+use crate::reload;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant, timeout_at};
+use tracing::{debug, info, warn};
+
+/// Quiet period after the last change before reloading, so editors that
+/// write in several steps only trigger one reload
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watch the config file and reload the daemon whenever it changes, as long
+/// as `server.watch_config` is on
+pub fn spawn(state: Arc<DaemonState>) -> notify::Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) => {
+            let _ = tx.send(event);
+        }
+        Err(err) => warn!("Chitin: config watcher error: {}", err),
+    })?;
+
+    let mut watched = WatchedFiles::default();
+    let paths = state.config.read().expect("config lock").watch_paths();
+    watched.sync(&mut watcher, paths);
+    // Contents last reloaded from, so repeated events for one change (or a
+    // change that failed to load) do not reload again
+    let mut loaded = watched.fingerprint();
+
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            if !watched.is_change(&event) {
+                continue;
+            }
+
+            // Wait until the file has been quiet for a while; other files in
+            // the same directory do not count
+            let mut deadline = Instant::now() + DEBOUNCE;
+            loop {
+                match timeout_at(deadline, rx.recv()).await {
+                    Ok(Some(event)) if watched.is_change(&event) => {
+                        deadline = Instant::now() + DEBOUNCE;
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+
+            if !state
+                .config
+                .read()
+                .expect("config lock")
+                .server
+                .watch_config
+            {
+                debug!("Chitin: config file changed, not reloading (watch_config is off)");
+                continue;
+            }
+            // Directories may have been created on the way to a config file
+            let paths = state.config.read().expect("config lock").watch_paths();
+            watched.sync(&mut watcher, paths);
+            let fingerprint = watched.fingerprint();
+            if fingerprint == loaded {
+                debug!("Chitin: config files unchanged, not reloading");
+                continue;
+            }
+            loaded = fingerprint;
+
+            info!("Chitin: config file changed, reloading...");
+            // Errors are logged by reload itself; the old provider stays active
+            let _ = reload::reload(&state).await;
+
+            let paths = state.config.read().expect("config lock").watch_paths();
+            watched.sync(&mut watcher, paths);
+        }
+    });
+
+    Ok(())
+}
+
+/// Config files of interest and the directories successfully watched to see them.
+///
+/// Directories are watched rather than the files themselves because many
+/// editors save by renaming a new file over the old one, which would silently
+/// end a watch on the original inode. Where a directory does not exist yet,
+/// its nearest existing ancestor is watched until it does.
+#[derive(Default)]
+struct WatchedFiles {
+    files: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl WatchedFiles {
+    fn sync(&mut self, watcher: &mut RecommendedWatcher, paths: Vec<PathBuf>) {
+        let files: HashSet<PathBuf> = paths
+            .iter()
+            .filter_map(|p| std::path::absolute(p).ok())
+            .collect();
+        let dirs: HashSet<PathBuf> = files
+            .iter()
+            .filter_map(|p| p.ancestors().skip(1).find(|dir| dir.is_dir()))
+            .map(Path::to_path_buf)
+            .collect();
+
+        self.dirs.retain(|dir| {
+            let keep = dirs.contains(dir);
+            if !keep {
+                let _ = watcher.unwatch(dir);
+            }
+            keep
+        });
+        for dir in dirs {
+            if self.dirs.contains(&dir) {
+                continue;
+            }
+            match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    debug!("Chitin: watching {} for config changes", dir.display());
+                    self.dirs.insert(dir);
+                }
+                Err(err) => warn!("Chitin: cannot watch {}: {}", dir.display(), err),
+            }
+        }
+
+        self.files = files;
+    }
+
+    /// Whether the event changed one of the config files, or a directory on
+    /// the way to one
+    fn is_change(&self, event: &Event) -> bool {
+        !event.kind.is_access()
+            && event
+                .paths
+                .iter()
+                .any(|p| self.files.iter().any(|file| file.starts_with(p)))
+    }
+
+    /// Contents of the config files, to tell whether any actually changed
+    fn fingerprint(&self) -> Vec<(PathBuf, Option<u64>)> {
+        let mut fingerprint: Vec<_> = self
+            .files
+            .iter()
+            .map(|file| {
+                let hash = std::fs::read(file).ok().map(|content| {
+                    let mut hasher = DefaultHasher::new();
+                    content.hash(&mut hasher);
+                    hasher.finish()
+                });
+                (file.clone(), hash)
+            })
+            .collect();
+        fingerprint.sort();
+        fingerprint
+    }
+}